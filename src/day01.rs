@@ -14,58 +14,193 @@
  * limitations under the License.
  */
 
-use std::fs;
+use std::collections::VecDeque;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::process;
 use std::string::String;
 use std::str::FromStr;
 
+mod lines;
+
+use lines::{LineError, Mode};
+
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
 
-    println!("{}", first_part(&str));
-    println!("{}", second_part(&str));
+    let mut mode = Mode::Strict;
+    let mut window = None;
+    let usage = "Usage: day01 [--lenient] [--window <size>], where size is at least 1";
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lenient" => mode = Mode::Lenient,
+            "--window" => {
+                let w: usize = args.next().and_then(|w| w.parse().ok()).expect(usage);
+                assert!(w > 0, "{}", usage);
+                window = Some(w);
+            },
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
+
+    if let Some(window) = window {
+        let file = File::open(filename).expect("Couldn't open file");
+        print!("{}", or_exit(analyse(BufReader::new(file), window, mode)));
+        return;
+    }
+
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
+
+    println!("{}", or_exit(first_part(&str, mode)));
+    println!("{}", or_exit(second_part(&str, mode)));
 }
 
-fn first_part(s: &str) -> isize {
-    let mut old : isize = -1;
-    let mut result : isize = -1;
-    for part in s.split("\n") {
-        let curr = match FromStr::from_str(part) {
-            Ok(i) => i,
-            Err(_) => continue,
-        };
+fn or_exit<T>(r: Result<T, AnalyseError>) -> T {
+    r.unwrap_or_else(|e| {
+        eprint!("{}", e);
+        process::exit(1)
+    })
+}
 
-        if curr > old {
-            result += 1
-        };
-        old = curr;
+#[derive(Debug)]
+enum AnalyseError {
+    Io(io::Error),
+    Malformed(Vec<LineError>),
+}
+
+impl From<io::Error> for AnalyseError {
+    fn from(e: io::Error) -> Self {
+        AnalyseError::Io(e)
+    }
+}
+
+impl fmt::Display for AnalyseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnalyseError::Io(e) => writeln!(f, "Couldn't read input: {}", e),
+            AnalyseError::Malformed(errors) => {
+                for e in errors {
+                    writeln!(f, "{}", e)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+fn parse_reading(s: &str) -> Result<isize, String> {
+    isize::from_str(s.trim())
+        .map_err(|e| format!("invalid reading {:?}: {}", s, e))
+}
+
+// Statistics over a stream of depth readings. Positions are 1-based
+// line numbers.
+#[derive(Debug, Default)]
+struct DepthStats {
+    window: usize,
+    readings: usize,
+    // Malformed lines skipped in lenient mode
+    discarded: usize,
+    // Number of times a window sum is larger than the previous one
+    increases: usize,
+    // (first line, number of readings) of the longest strictly
+    // increasing run
+    longest_run: (usize, usize),
+    // (line, size) of the largest drop between consecutive readings
+    largest_drop: Option<(usize, isize)>,
+}
+
+impl fmt::Display for DepthStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Readings: {}", self.readings)?;
+        if self.discarded > 0 {
+            writeln!(f, "Discarded lines: {}", self.discarded)?;
+        }
+        writeln!(f, "Increases with window {}: {}", self.window, self.increases)?;
+        writeln!(f, "Longest increasing run: {} readings starting at line {}",
+                 self.longest_run.1, self.longest_run.0)?;
+        match self.largest_drop {
+            Some((line, size)) =>
+                writeln!(f, "Largest drop: {} at line {}", size, line),
+            None => writeln!(f, "Largest drop: none"),
+        }
     }
-    result
 }
 
-fn second_part(s: &str) -> isize {
-    let mut nums = vec!();
+// Consecutive sums over a window of size w only differ by the
+// readings entering and leaving the window, so comparing the sums is
+// the same as comparing nums[i] with nums[i+w]. We only need to keep
+// the last w readings around.
+fn analyse<R: BufRead>(r: R, window: usize, mode: Mode)
+                       -> Result<DepthStats, AnalyseError> {
+    assert!(window > 0, "Window must be at least 1");
 
-    for part in s.split("\n") {
-        let i : isize = match FromStr::from_str(part) {
+    let mut stats = DepthStats { window, ..Default::default() };
+    let mut buf = VecDeque::with_capacity(window);
+    let mut prev: Option<isize> = None;
+    let mut run_start = 0;
+    let mut run_len = 0;
+    let mut errors = Vec::new();
+
+    for (idx, line) in r.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue
+        }
+        let curr = match parse_reading(&line) {
             Ok(i) => i,
-            Err(_) => continue,
+            Err(reason) => {
+                stats.discarded += 1;
+                errors.push(LineError { line: line_no, reason });
+                continue
+            },
         };
+        stats.readings += 1;
+
+        if buf.len() == window && curr > buf.pop_front().unwrap() {
+            stats.increases += 1;
+        }
+        buf.push_back(curr);
 
-        nums.push(i);
+        match prev {
+            Some(p) if curr > p => run_len += 1,
+            _ => {
+                run_start = line_no;
+                run_len = 1;
+            },
+        }
+        if run_len > stats.longest_run.1 {
+            stats.longest_run = (run_start, run_len);
+        }
+
+        if let Some(p) = prev {
+            let drop = p - curr;
+            if drop > 0 && stats.largest_drop.is_none_or(|(_, d)| drop > d) {
+                stats.largest_drop = Some((line_no, drop));
+            }
+        }
+        prev = Some(curr);
     }
 
-    let mut old_sum = -1;
-    let mut result = -1;
-    for i in 0..(nums.len()-2) {
-        let new_sum = nums[i] + nums[i+1] + nums[i+2];
+    if mode == Mode::Strict && !errors.is_empty() {
+        return Err(AnalyseError::Malformed(errors));
+    }
+    Ok(stats)
+}
 
-        if new_sum > old_sum {
-            result += 1
-        };
-        old_sum = new_sum;
+fn first_part(s: &str, mode: Mode) -> Result<usize, AnalyseError> {
+    let stats = analyse(s.as_bytes(), 1, mode)?;
+    if stats.discarded > 0 {
+        eprintln!("Discarded {} malformed lines", stats.discarded);
     }
+    Ok(stats.increases)
+}
 
-    result
+fn second_part(s: &str, mode: Mode) -> Result<usize, AnalyseError> {
+    Ok(analyse(s.as_bytes(), 3, mode)?.increases)
 }
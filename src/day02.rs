@@ -14,75 +14,377 @@
  * limitations under the License.
  */
 
+use std::env;
+use std::fmt;
 use std::fs;
+use std::process;
 use std::string::String;
 use std::str::FromStr;
 
+mod lines;
+
+use lines::{LineError, Mode};
+
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
+
+    let mut mode = Mode::Strict;
+    let mut csv: Option<Box<dyn Model>> = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lenient" => mode = Mode::Lenient,
+            "--csv" => csv = match args.next().as_deref() {
+                Some("direct") => Some(Box::new(Direct)),
+                Some("aim") => Some(Box::new(Aim)),
+                _ => panic!("Usage: day02 [--lenient] [--csv <direct|aim>]"),
+            },
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
 
-    println!("{}", first_part(&str));
-    println!("{}", second_part(&str));
+    let course = parse(&str, mode).unwrap_or_else(|errors| {
+        for e in errors {
+            eprintln!("{}", e);
+        }
+        process::exit(1)
+    });
+    if course.discarded > 0 {
+        eprintln!("Discarded {} malformed lines", course.discarded);
+    }
+
+    if let Some(model) = csv {
+        print!("{}", to_csv(&run(&course.program, model.as_ref())));
+        return;
+    }
+
+    println!("{}", first_part(&course.program));
+    println!("{}", second_part(&course.program));
 }
 
-fn first_part(s: &str) -> isize {
-    let mut horz = 0;
-    let mut depth = 0;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Up(isize),
+    Down(isize),
+    Forward(isize),
+    Back(isize),
+    Surface,
+}
 
-    for part in s.split("\n") {
-        let idx = match part.find(" ") {
-            Some(i) => i,
-            None => continue,
-        };
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Stmt {
+    Command(Command),
+    Repeat(usize, Vec<Stmt>),
+}
+
+// A course is a sequence of statements, one or more per line:
+//
+//   forward 5      # comments run to the end of the line
+//   repeat 3 {
+//     down 2
+//     back 1
+//   }
+//   surface
+//
+// Commands and their arguments must be on the same line.
+struct Parser {
+    tokens: Vec<(usize, String)>,
+    pos: usize,
+    errors: Vec<LineError>,
+}
+
+impl Parser {
+    fn new(s: &str) -> Self {
+        let mut tokens = Vec::new();
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap()
+                .replace('{', " { ").replace('}', " } ");
+            for tok in line.split_whitespace() {
+                tokens.push((idx + 1, tok.to_string()));
+            }
+        }
+        Parser { tokens, pos: 0, errors: Vec::new() }
+    }
+
+    fn next(&mut self) -> Option<(usize, String)> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
 
-        let n = match isize::from_str(part.split_at(idx+1).1) {
-            Ok(i) => i,
-            Err(_) => continue,
+    // Take the next token, but only if it's on the given line.
+    fn take(&mut self, line: usize) -> Option<String> {
+        match self.tokens.get(self.pos) {
+            Some((l, tok)) if *l == line => {
+                self.pos += 1;
+                Some(tok.clone())
+            },
+            _ => None,
+        }
+    }
+
+    // Skip the rest of a bad statement. Inside a repeat this stops
+    // before a closing brace on the same line, which is left for the
+    // block to close.
+    fn skip_line(&mut self, line: usize, in_repeat: bool) {
+        while let Some((l, tok)) = self.tokens.get(self.pos) {
+            if *l != line || in_repeat && tok == "}" {
+                break
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn number<T: FromStr>(&mut self, line: usize, cmd: &str) -> Result<T, String>
+        where T::Err: fmt::Display {
+        let n = self.take(line)
+            .ok_or(format!("expected an amount after {:?}", cmd))?;
+        T::from_str(&n).map_err(|e| format!("invalid amount {:?}: {}", n, e))
+    }
+
+    // The header runs up to the opening brace. Its block is parsed even
+    // if the header is bad, so that the block is dropped as a whole and
+    // its closing brace isn't left behind.
+    fn repeat(&mut self, line: usize) -> Result<Stmt, String> {
+        let mut header = Vec::new();
+        let mut opened = false;
+        while let Some((l, tok)) = self.tokens.get(self.pos) {
+            if *l != line || tok == "}" {
+                break
+            }
+            self.pos += 1;
+            if tok == "{" {
+                opened = true;
+                break
+            }
+            header.push(tok.clone());
+        }
+        let body = if opened { self.block(Some(line)) } else { Vec::new() };
+
+        let n = match &header[..] {
+            [] => return Err("expected an amount after \"repeat\"".to_string()),
+            [n, ..] => n.parse().map_err(|e| format!("invalid amount {:?}: {}", n, e))?,
         };
+        if header.len() > 1 || !opened {
+            return Err("expected `{` after `repeat <count>`".to_string());
+        }
+        Ok(Stmt::Repeat(n, body))
+    }
+
+    // Parse statements up to the end of input, or the closing brace if
+    // we're inside a repeat opened on the given line.
+    fn block(&mut self, opened: Option<usize>) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
+        loop {
+            let (line, tok) = match self.next() {
+                Some(t) => t,
+                None => {
+                    if let Some(line) = opened {
+                        let reason = "unclosed `repeat` block".to_string();
+                        self.errors.push(LineError { line, reason });
+                    }
+                    return stmts
+                },
+            };
 
-        if part.starts_with("up") {
-            depth -= n;
-        } else if part.starts_with("down") {
-            depth += n;
-        } else if part.starts_with("forward") {
-            horz += n;
-        } else {
-            continue
+            let result = match tok.as_str() {
+                "}" if opened.is_some() => return stmts,
+                "}" => Err("unmatched `}`".to_string()),
+                "up" => self.number(line, &tok).map(Command::Up).map(Stmt::Command),
+                "down" => self.number(line, &tok).map(Command::Down).map(Stmt::Command),
+                "forward" => self.number(line, &tok).map(Command::Forward).map(Stmt::Command),
+                "back" => self.number(line, &tok).map(Command::Back).map(Stmt::Command),
+                "surface" => Ok(Stmt::Command(Command::Surface)),
+                "repeat" => self.repeat(line),
+                _ => Err(format!("unknown command {:?}", tok)),
+            };
+
+            match result {
+                Ok(stmt) => stmts.push(stmt),
+                Err(reason) => {
+                    self.errors.push(LineError { line, reason });
+                    // A bad repeat has already skipped its whole block
+                    if tok != "repeat" {
+                        self.skip_line(line, opened.is_some());
+                    }
+                },
+            }
         }
-    };
+    }
+}
 
-    horz*depth
+struct Course {
+    program: Vec<Stmt>,
+    discarded: usize,
 }
 
-fn second_part(s: &str) -> isize {
-    let mut horz = 0;
-    let mut depth = 0;
-    let mut aim = 0;
+fn parse(s: &str, mode: Mode) -> Result<Course, Vec<LineError>> {
+    let mut parser = Parser::new(s);
+    let program = parser.block(None);
 
-    for part in s.split("\n") {
-        let idx = match part.find(" ") {
-            Some(i) => i,
-            None => continue,
-        };
+    if mode == Mode::Strict && !parser.errors.is_empty() {
+        return Err(parser.errors);
+    }
+    let mut lines: Vec<usize> = parser.errors.iter().map(|e| e.line).collect();
+    lines.sort_unstable();
+    lines.dedup();
+    Ok(Course { program, discarded: lines.len() })
+}
 
-        let n = match isize::from_str(part.split_at(idx+1).1) {
-            Ok(i) => i,
-            Err(_) => continue,
-        };
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Submarine {
+    horz: isize,
+    depth: isize,
+    aim: isize,
+}
+
+// How a submarine interprets a command. Any closure taking a
+// submarine and a command can be used as a model.
+trait Model {
+    fn apply(&self, sub: Submarine, cmd: Command) -> Submarine;
+}
+
+impl<F> Model for F
+    where F: Fn(Submarine, Command) -> Submarine {
+    fn apply(&self, sub: Submarine, cmd: Command) -> Submarine {
+        self(sub, cmd)
+    }
+}
+
+// Part 1: up and down change the depth directly.
+struct Direct;
 
-        if part.starts_with("up") {
-            aim -= n;
-        } else if part.starts_with("down") {
-            aim += n;
-        } else if part.starts_with("forward") {
-            horz += n;
-            depth += aim * n;
-        } else {
-            continue
+impl Model for Direct {
+    fn apply(&self, mut sub: Submarine, cmd: Command) -> Submarine {
+        match cmd {
+            Command::Up(n) => sub.depth -= n,
+            Command::Down(n) => sub.depth += n,
+            Command::Forward(n) => sub.horz += n,
+            Command::Back(n) => sub.horz -= n,
+            Command::Surface => sub.depth = 0,
         }
-    };
+        sub
+    }
+}
+
+// Part 2: up and down change the aim, which is applied when moving.
+struct Aim;
+
+impl Model for Aim {
+    fn apply(&self, mut sub: Submarine, cmd: Command) -> Submarine {
+        match cmd {
+            Command::Up(n) => sub.aim -= n,
+            Command::Down(n) => sub.aim += n,
+            Command::Forward(n) => {
+                sub.horz += n;
+                sub.depth += sub.aim * n;
+            },
+            Command::Back(n) => {
+                sub.horz -= n;
+                sub.depth -= sub.aim * n;
+            },
+            Command::Surface => {
+                sub.depth = 0;
+                sub.aim = 0;
+            },
+        }
+        sub
+    }
+}
+
+fn replay(program: &[Stmt], model: &dyn Model, trajectory: &mut Vec<Submarine>) {
+    for stmt in program {
+        match stmt {
+            Stmt::Command(cmd) => {
+                let sub = *trajectory.last().unwrap();
+                trajectory.push(model.apply(sub, *cmd));
+            },
+            Stmt::Repeat(n, body) => {
+                for _ in 0..*n {
+                    replay(body, model, trajectory);
+                }
+            },
+        }
+    }
+}
+
+// Run the program from the surface, returning the state before the
+// first command and after each command.
+fn run(program: &[Stmt], model: &dyn Model) -> Vec<Submarine> {
+    let mut trajectory = vec![Submarine::default()];
+    replay(program, model, &mut trajectory);
+    trajectory
+}
+
+fn to_csv(trajectory: &[Submarine]) -> String {
+    let mut out = "step,horizontal,depth,aim\n".to_string();
+    for (idx, sub) in trajectory.iter().enumerate() {
+        out += &format!("{},{},{},{}\n", idx, sub.horz, sub.depth, sub.aim);
+    }
+    out
+}
+
+fn first_part(program: &[Stmt]) -> isize {
+    let sub = *run(program, &Direct).last().unwrap();
+    sub.horz*sub.depth
+}
+
+fn second_part(program: &[Stmt]) -> isize {
+    let sub = *run(program, &Aim).last().unwrap();
+    sub.horz*sub.depth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BAD_REPEAT: &str = "repeat 2 { forwrd 1 }\ndown 3\n";
+
+    #[test]
+    fn bad_command_in_repeat_strict() {
+        let errors = parse(BAD_REPEAT, Mode::Strict).err().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 1);
+        assert_eq!(errors[0].reason, "unknown command \"forwrd\"");
+    }
+
+    #[test]
+    fn bad_command_in_repeat_lenient() {
+        let course = parse(BAD_REPEAT, Mode::Lenient).ok().unwrap();
+        assert_eq!(course.discarded, 1);
+        assert_eq!(course.program, vec![
+            Stmt::Repeat(2, Vec::new()),
+            Stmt::Command(Command::Down(3)),
+        ]);
+        assert_eq!(run(&course.program, &Direct).last().unwrap().depth, 3);
+    }
+
+    const BAD_HEADER: &str = "repeat x {\n down 2\n}\nforward 1\n";
+
+    #[test]
+    fn bad_repeat_header_strict() {
+        let errors = parse(BAD_HEADER, Mode::Strict).err().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 1);
+        assert!(errors[0].reason.starts_with("invalid amount \"x\""));
+    }
+
+    #[test]
+    fn bad_repeat_header_lenient() {
+        let course = parse(BAD_HEADER, Mode::Lenient).ok().unwrap();
+        assert_eq!(course.discarded, 1);
+        assert_eq!(course.program, vec![Stmt::Command(Command::Forward(1))]);
+    }
 
-    horz*depth
+    #[test]
+    fn discarded_counts_lines() {
+        let course = parse("repeat 2 { forwrd 1 } up x\ndown 3\n", Mode::Lenient).ok().unwrap();
+        assert_eq!(course.discarded, 1);
+        assert_eq!(course.program, vec![
+            Stmt::Repeat(2, Vec::new()),
+            Stmt::Command(Command::Down(3)),
+        ]);
+    }
 }
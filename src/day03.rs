@@ -14,118 +14,318 @@
  * limitations under the License.
  */
 
+use std::env;
+use std::fmt;
 use std::fs;
 use std::string::String;
+use std::str::FromStr;
+use num_bigint::BigUint;
 
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
+
+    let mut args = env::args().skip(1);
+    match args.next().as_deref() {
+        Some("--prefix") => {
+            let diag: Diagnostic = str.parse().expect("Couldn't parse report");
+            let trie = Trie::new(&diag.words);
+            for prefix in args {
+                println!("{}: {}", prefix, trie.prefix_count(&prefix));
+            }
+            return;
+        },
+        Some("--report") => {
+            let policy = args.next().map_or(Ok(TiePolicy::One), |p| p.parse())
+                .expect("Usage: day03 --report [one|zero|reject]");
+            let diag: Diagnostic = str.parse().expect("Couldn't parse report");
+            print!("{}", report(&diag, policy));
+            return;
+        },
+        _ => (),
+    }
 
     println!("{}", first_part(&str));
     println!("{}", second_part(&str));
 }
 
-fn count(v: &Vec<&str>) -> Vec<usize> {
-    let mut ret = Vec::with_capacity(v[0].len());
-    for _ in 0..v[0].len() {
-        ret.push(0);
+// A diagnostic reading of any width, stored as a bitset. Bit 0 is the
+// leftmost (most significant) bit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Word {
+    width: usize,
+    bits: Vec<u64>,
+}
+
+impl Word {
+    fn zero(width: usize) -> Self {
+        Word { width, bits: vec![0; width.div_ceil(64)] }
     }
 
-    for s in v {
-        let mut idx = 0;
-        for c in s.chars() {
+    fn get(&self, idx: usize) -> bool {
+        self.bits[idx / 64] >> (idx % 64) & 1 == 1
+    }
+
+    fn set(&mut self, idx: usize, val: bool) {
+        if val {
+            self.bits[idx / 64] |= 1 << (idx % 64);
+        } else {
+            self.bits[idx / 64] &= !(1 << (idx % 64));
+        }
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        let digits: Vec<u8> = (0..self.width).map(|i| self.get(i) as u8).collect();
+        BigUint::from_radix_be(&digits, 2).unwrap_or_default()
+    }
+}
+
+impl FromStr for Word {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut word = Word::zero(s.len());
+        for (idx, c) in s.chars().enumerate() {
             match c {
-                '1' => ret[idx] += 1,
+                '1' => word.set(idx, true),
                 '0' => (),
-                _ => panic!(),
+                _ => return Err(format!("invalid bit {:?} in {:?}", c, s)),
             }
-            idx += 1;
         }
+        Ok(word)
     }
-
-    ret
 }
 
-fn first_part(s: &str) -> isize {
-    let lines = s.split_terminator("\n").collect();
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for idx in 0..self.width {
+            write!(f, "{}", if self.get(idx) { '1' } else { '0' })?;
+        }
+        Ok(())
+    }
+}
 
-    let count = count(&lines);
+// Which bit counts as the most common when a column is evenly split.
+// The least common bit is always the other one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TiePolicy {
+    One,
+    Zero,
+    Reject,
+}
 
-    let threshold = lines.len() / 2;
-    let mut gamma = 0;
-    let mut epsilon = 0;
+impl FromStr for TiePolicy {
+    type Err = String;
 
-    for i in count {
-        gamma *= 2;
-        epsilon *= 2;
-        if i > threshold {
-            gamma += 1;
-        } else {
-            epsilon += 1;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "one" => Ok(TiePolicy::One),
+            "zero" => Ok(TiePolicy::Zero),
+            "reject" => Ok(TiePolicy::Reject),
+            _ => Err(format!("Unknown tie policy {}", s)),
         }
     }
-
-    gamma * epsilon
 }
 
-fn str_to_int(s: &str) -> isize {
-    let mut ret = 0;
-    for c in s.chars() {
-        ret *= 2;
-        match c {
-            '1' => ret += 1,
-            _ => (),
-        }
+fn most_common(zeros: usize, ones: usize, policy: TiePolicy) -> Result<bool, String> {
+    if ones != zeros {
+        return Ok(ones > zeros);
+    }
+    match policy {
+        TiePolicy::One => Ok(true),
+        TiePolicy::Zero => Ok(false),
+        TiePolicy::Reject => Err(format!("tie with {} of each bit", ones)),
     }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Column {
+    zeros: usize,
+    ones: usize,
+}
 
-    ret
+struct Diagnostic {
+    width: usize,
+    words: Vec<Word>,
 }
 
-fn second_part(s: &str) -> isize {
-    let mut co2 : Vec<&str> = s.split_terminator("\n").collect();
-    let mut o_gen = co2.clone();
+impl FromStr for Diagnostic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_terminator('\n').enumerate().map(|(idx, line)| {
+            line.parse::<Word>().map_err(|e| format!("line {}: {}", idx + 1, e))
+        }).collect::<Result<Vec<_>, _>>()?;
 
-    for idx in 0..co2[0].len() {
-        let mut new_co2 = Vec::new();
+        let width = words.first().ok_or("Empty report")?.width;
+        if let Some(idx) = words.iter().position(|w| w.width != width) {
+            return Err(format!("line {}: expected {} bits", idx + 1, width));
+        }
+        Ok(Diagnostic { width, words })
+    }
+}
 
-        let count = count(&co2);
-        let threshold = (co2.len()+1) / 2;
-        for s in co2 {
-            match s.as_bytes()[idx] {
-                48 => if count[idx] >= threshold {new_co2.push(s)} ,
-                49 => if count[idx] < threshold {new_co2.push(s)},
-                _ => panic!(),
+impl Diagnostic {
+    fn columns(&self) -> Vec<Column> {
+        let mut ret = vec![Column::default(); self.width];
+        for word in &self.words {
+            for (idx, col) in ret.iter_mut().enumerate() {
+                if word.get(idx) {
+                    col.ones += 1;
+                } else {
+                    col.zeros += 1;
+                }
             }
         }
+        ret
+    }
 
-        co2 = new_co2;
+    fn gamma(&self, policy: TiePolicy) -> Result<Word, String> {
+        let mut gamma = Word::zero(self.width);
+        for (idx, col) in self.columns().into_iter().enumerate() {
+            let bit = most_common(col.zeros, col.ones, policy)
+                .map_err(|e| format!("column {}: {}", idx, e))?;
+            gamma.set(idx, bit);
+        }
+        Ok(gamma)
+    }
 
-        if co2.len() == 1 {
-            break
+    fn epsilon(&self, policy: TiePolicy) -> Result<Word, String> {
+        let mut epsilon = self.gamma(policy)?;
+        for idx in 0..self.width {
+            epsilon.set(idx, !epsilon.get(idx));
         }
+        Ok(epsilon)
     }
+}
+
+#[derive(Default)]
+struct Node {
+    count: usize,
+    children: [Option<usize>; 2],
+}
 
-    for idx in 0..o_gen[0].len() {
-        let mut new_o_gen = Vec::new();
+// Binary trie over the readings, where each node records how many
+// readings share the prefix leading to it.
+struct Trie {
+    width: usize,
+    nodes: Vec<Node>,
+}
 
-        let count = count(&o_gen);
-        let threshold = (o_gen.len()+1) / 2;
-        for s in o_gen {
-            match s.as_bytes()[idx] {
-                48 => if count[idx] < threshold {new_o_gen.push(s)} ,
-                49 => if count[idx] >= threshold {new_o_gen.push(s)},
-                _ => panic!(),
-            }
+impl Trie {
+    fn new(words: &[Word]) -> Self {
+        let width = words.first().map_or(0, |w| w.width);
+        let mut trie = Trie { width, nodes: vec![Node::default()] };
+        for w in words {
+            trie.insert(w);
+        }
+        trie
+    }
+
+    fn insert(&mut self, word: &Word) {
+        let mut idx = 0;
+        self.nodes[idx].count += 1;
+        for i in 0..word.width {
+            let bit = word.get(i) as usize;
+            idx = match self.nodes[idx].children[bit] {
+                Some(child) => child,
+                None => {
+                    self.nodes.push(Node::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[idx].children[bit] = Some(child);
+                    child
+                },
+            };
+            self.nodes[idx].count += 1;
+        }
+    }
+
+    fn child_count(&self, idx: usize, bit: usize) -> usize {
+        self.nodes[idx].children[bit].map_or(0, |c| self.nodes[c].count)
+    }
+
+    // Number of readings starting with the given prefix.
+    fn prefix_count(&self, prefix: &str) -> usize {
+        let mut idx = 0;
+        for c in prefix.chars() {
+            let bit = if c == '1' { 1 } else { 0 };
+            idx = match self.nodes[idx].children[bit] {
+                Some(child) => child,
+                None => return 0,
+            };
+        }
+        self.nodes[idx].count
+    }
+
+    // Walk from the root to a leaf, at each step picking a bit given
+    // the number of readings below each child. Empty children are
+    // never picked.
+    fn walk<F>(&self, pick: F) -> Result<Word, String>
+        where F: Fn(usize, usize) -> Result<bool, String> {
+        let mut idx = 0;
+        let mut result = Word::zero(self.width);
+        for i in 0..self.width {
+            let zeros = self.child_count(idx, 0);
+            let ones = self.child_count(idx, 1);
+            let bit = match (zeros, ones) {
+                (0, 0) => return Err("Empty report".to_string()),
+                (0, _) => true,
+                (_, 0) => false,
+                _ => pick(zeros, ones).map_err(|e| format!("column {}: {}", i, e))?,
+            };
+            result.set(i, bit);
+            idx = self.nodes[idx].children[bit as usize].unwrap();
         }
+        Ok(result)
+    }
+
+    fn oxygen_rating(&self, policy: TiePolicy) -> Result<Word, String> {
+        self.walk(|zeros, ones| most_common(zeros, ones, policy))
+    }
 
-        o_gen = new_o_gen;
+    fn co2_rating(&self, policy: TiePolicy) -> Result<Word, String> {
+        self.walk(|zeros, ones| most_common(zeros, ones, policy).map(|b| !b))
+    }
+}
+
+fn report(diag: &Diagnostic, policy: TiePolicy) -> String {
+    let mut out = "column,zeros,ones\n".to_string();
+    for (idx, col) in diag.columns().iter().enumerate() {
+        out += &format!("{},{},{}\n", idx, col.zeros, col.ones);
+    }
 
-        if o_gen.len() == 1 {
-            break
+    let trie = Trie::new(&diag.words);
+    let values = [
+        ("gamma", diag.gamma(policy)),
+        ("epsilon", diag.epsilon(policy)),
+        ("oxygen", trie.oxygen_rating(policy)),
+        ("co2", trie.co2_rating(policy)),
+    ];
+    for (name, value) in values {
+        match value {
+            Ok(w) => out += &format!("{}: {} ({})\n", name, w, w.to_biguint()),
+            Err(e) => out += &format!("{}: {}\n", name, e),
         }
     }
+    out
+}
+
+// The puzzle treats an evenly split column as having 0 as the most
+// common bit here, but 1 when finding the ratings.
+fn first_part(s: &str) -> BigUint {
+    let diag: Diagnostic = s.parse().expect("Couldn't parse report");
+    let gamma = diag.gamma(TiePolicy::Zero).unwrap();
+    let epsilon = diag.epsilon(TiePolicy::Zero).unwrap();
 
-    str_to_int(o_gen[0]) * str_to_int(co2[0])
+    gamma.to_biguint() * epsilon.to_biguint()
 }
 
+fn second_part(s: &str) -> BigUint {
+    let diag: Diagnostic = s.parse().expect("Couldn't parse report");
+    let trie = Trie::new(&diag.words);
+    let oxygen = trie.oxygen_rating(TiePolicy::One).unwrap();
+    let co2 = trie.co2_rating(TiePolicy::One).unwrap();
+
+    oxygen.to_biguint() * co2.to_biguint()
+}
@@ -17,22 +17,186 @@
 use std::fs;
 use std::string::String;
 use std::str::FromStr;
-use std::convert::TryInto;
+use std::env;
+use std::collections::HashMap;
+
+mod repl;
 
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
+
+    let mut interactive = false;
+    let mut timeline = false;
+    let mut rules = Rules::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--interactive" => interactive = true,
+            "--timeline" => timeline = true,
+            "--diagonals" => rules.diagonals = true,
+            "--pattern" => {
+                let pattern = args.next()
+                    .expect("Usage: day04 --pattern <corners|x|full|mask file>");
+                rules.patterns.push(match pattern.as_str() {
+                    "corners" | "x" | "full" => pattern,
+                    _ => fs::read_to_string(&pattern)
+                        .expect("Couldn't read mask file"),
+                });
+            },
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
+
+    if interactive {
+        repl::run(rules.parse_game(&str).expect("Couldn't parse game"));
+        return;
+    }
+
+    if timeline {
+        let game = rules.parse_game(&str).expect("Couldn't parse game");
+        print!("{}", timeline_csv(&game.play()));
+        return;
+    }
+
+    println!("{}", first_part(&str, &rules));
+    println!("{}", second_part(&str, &rules));
+}
+
+// A win pattern is a mask over the squares of a board. A board wins
+// once every square in one of its patterns has been called.
+type Mask = Vec<bool>;
 
-    println!("{}", first_part(&str));
-    println!("{}", second_part(&str));
+#[derive(Clone)]
+struct Pattern {
+    name: String,
+    mask: Mask,
 }
 
+fn line_patterns(size: usize) -> Vec<Pattern> {
+    let mut result = Vec::new();
+    for i in 0..size {
+        result.push(Pattern {
+            name: format!("row {}", i),
+            mask: (0..size*size).map(|sq| sq / size == i).collect(),
+        });
+        result.push(Pattern {
+            name: format!("column {}", i),
+            mask: (0..size*size).map(|sq| sq % size == i).collect(),
+        });
+    }
+    result
+}
+
+fn diagonal_masks(size: usize) -> Vec<Mask> {
+    vec![
+        (0..size*size).map(|sq| sq / size == sq % size).collect(),
+        (0..size*size).map(|sq| sq / size + sq % size == size - 1).collect(),
+    ]
+}
+
+fn diagonal_patterns(size: usize) -> Vec<Pattern> {
+    let names = ["diagonal", "anti-diagonal"];
+    names.iter().zip(diagonal_masks(size)).map(|(name, mask)| {
+        Pattern { name: name.to_string(), mask }
+    }).collect()
+}
+
+// Either the name of a preset pattern or a grid with 'X' for squares
+// in the pattern and '.' for squares that aren't.
+fn parse_mask(s: &str, size: usize) -> Result<Mask, &'static str> {
+    let edges = [0, size - 1];
+    let corner = |sq: usize| edges.contains(&(sq / size)) && edges.contains(&(sq % size));
+
+    let mask: Mask = match s {
+        "corners" => (0..size*size).map(corner).collect(),
+        "x" => {
+            let diags = diagonal_masks(size);
+            diags[0].iter().zip(&diags[1]).map(|(&a, &b)| a || b).collect()
+        },
+        "full" => vec![true; size*size],
+        _ => {
+            let rows: Vec<_> = s.split_whitespace().collect();
+            if rows.len() != size || rows.iter().any(|r| r.chars().count() != size) {
+                return Err("Mask doesn't match the board size");
+            }
+            rows.iter().flat_map(|r| r.chars()).map(|c| match c {
+                'X' | 'x' | '#' => Ok(true),
+                '.' => Ok(false),
+                _ => Err("Mask contained something other than 'X' or '.'"),
+            }).collect::<Result<_, _>>()?
+        },
+    };
+
+    if !mask.contains(&true) {
+        return Err("Mask is empty");
+    }
+    Ok(mask)
+}
+
+// Which patterns count as a win. Rows and columns always win unless
+// custom patterns are given, as house variants are played on their
+// own.
+#[derive(Default)]
+struct Rules {
+    diagonals: bool,
+    patterns: Vec<String>,
+}
+
+impl Rules {
+    fn parse_game(&self, s: &str) -> Result<Game, &'static str> {
+        let mut game: Game = s.parse()?;
+
+        let mut patterns = if self.patterns.is_empty() {
+            line_patterns(game.size)
+        } else {
+            self.patterns.iter().enumerate().map(|(idx, p)| {
+                let name = match p.as_str() {
+                    "corners" | "x" | "full" => p.clone(),
+                    _ => format!("mask {}", idx),
+                };
+                Ok(Pattern { name, mask: parse_mask(p, game.size)? })
+            }).collect::<Result<_, _>>()?
+        };
+        if self.diagonals {
+            patterns.extend(diagonal_patterns(game.size));
+        }
+        game.set_patterns(patterns);
+        Ok(game)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Win {
+    turn: usize,
+    board: usize,
+    number: isize,
+    pattern: String,
+    score: isize,
+}
+
+#[derive(Clone)]
 struct Game {
     nums: Vec<isize>,
-    boards: Vec<[isize; 25]>,
-    state: Vec<[bool; 25]>,
+    size: usize,
+    boards: Vec<Vec<isize>>,
+    state: Vec<Vec<bool>>,
+    patterns: Vec<Pattern>,
     just_called: Option<isize>,
+    turn: usize,
+    timeline: Vec<Win>,
+
+    // Which squares each number appears in, as (board, square)
+    index: HashMap<isize, Vec<(usize, usize)>>,
+    // Which patterns each square is part of, and how many squares
+    // each pattern has
+    square_patterns: Vec<Vec<usize>>,
+    pattern_sizes: Vec<usize>,
+    // Number of marked squares per board and pattern
+    hits: Vec<Vec<usize>>,
+    unmarked: Vec<isize>,
+    won: Vec<bool>,
 }
 
 impl FromStr for Game {
@@ -46,119 +210,177 @@ impl FromStr for Game {
             .rev().collect::<Result<_,_>>()
             .or(Err("Couldn't parse move list"))?;
 
-        let boards =
+        let size = boards_string.trim_start().lines().next()
+            .ok_or("Couldn't find any boards")?
+            .split_whitespace().count();
+
+        let boards: Vec<Vec<isize>> =
             boards_string.split_terminator("\n\n").skip(1).map(|board| {
-                board.split_whitespace().map(|n| n.parse())
+                if board.lines().count() != size {
+                    return Err("Board isn't square");
+                }
+                let board = board.split_whitespace().map(|n| n.parse())
                     .collect::<Result<Vec<_>,_>>().or(
                         Err("Board contained non-integer")
-                    )?.try_into().or(
-                        Err("Board doesn't have 25 squares")
-                    )
+                    )?;
+                if board.len() != size*size {
+                    return Err("Board isn't square");
+                }
+                Ok(board)
             }).collect::<Result<Vec<_>, _>>()?;
 
-        let mut state = Vec::new();
-        for _ in 0..boards.len() {
-            state.push([false; 25])
+        let mut index = HashMap::new();
+        for (board_idx, board) in boards.iter().enumerate() {
+            for (sq_idx, &sq) in board.iter().enumerate() {
+                index.entry(sq).or_insert_with(Vec::new).push((board_idx, sq_idx));
+            }
         }
 
-        Ok(Game { nums, boards, state, just_called : None })
+        let state = vec![vec![false; size*size]; boards.len()];
+        let unmarked = boards.iter().map(|b| b.iter().sum()).collect();
+        let won = vec![false; boards.len()];
+
+        let mut game = Game {
+            nums, size, boards, state, patterns: Vec::new(),
+            just_called: None, turn: 0, timeline: Vec::new(),
+            index, square_patterns: Vec::new(), pattern_sizes: Vec::new(),
+            hits: Vec::new(),
+            unmarked, won,
+        };
+        game.set_patterns(line_patterns(size));
+        Ok(game)
     }
 }
 
 impl Game {
+    // Should be called before any numbers are drawn.
+    fn set_patterns(&mut self, patterns: Vec<Pattern>) {
+        self.square_patterns = vec![Vec::new(); self.size*self.size];
+        for (p_idx, p) in patterns.iter().enumerate() {
+            for (sq, &m) in p.mask.iter().enumerate() {
+                if m {
+                    self.square_patterns[sq].push(p_idx);
+                }
+            }
+        }
+        self.pattern_sizes = patterns.iter()
+            .map(|p| p.mask.iter().filter(|&&m| m).count()).collect();
+        self.hits = vec![vec![0; patterns.len()]; self.boards.len()];
+        self.patterns = patterns;
+    }
+
     fn run_step(&mut self) -> Result<(), &'static str> {
         let n = self.nums.pop().ok_or("Out of moves!")?;
 
         self.just_called = Some(n);
+        self.turn += 1;
 
-        for (board_idx, board) in self.boards.iter().enumerate() {
-            for (sq_idx, sq) in board.iter().enumerate() {
-                if *sq == n {
-                    self.state[board_idx][sq_idx] = true;
+        let mut winners = Vec::new();
+        for &(board_idx, sq_idx) in self.index.get(&n).into_iter().flatten() {
+            if self.state[board_idx][sq_idx] {
+                continue
+            }
+            self.state[board_idx][sq_idx] = true;
+            self.unmarked[board_idx] -= n;
+
+            for &p in &self.square_patterns[sq_idx] {
+                self.hits[board_idx][p] += 1;
+                if self.hits[board_idx][p] == self.pattern_sizes[p] &&
+                    !self.won[board_idx] {
+                    self.won[board_idx] = true;
+                    winners.push((board_idx, p));
                 }
             }
         }
 
+        // Scores are only final once every square for this number
+        // has been marked.
+        for (board, p) in winners {
+            self.timeline.push(Win {
+                turn: self.turn,
+                board,
+                number: n,
+                pattern: self.patterns[p].name.clone(),
+                score: self.unmarked[board] * n,
+            });
+        }
+
         Ok(())
     }
 
-    fn is_winning(&self, idx: usize) -> bool {
-        'outer1: for row in 0..5 {
-            for col in 0..5 {
-                if !self.state[idx][row*5 + col] {
-                    continue 'outer1
-                }
-            }
-            return true;
-        }
+    // Draw every remaining number, returning the wins in the order
+    // they happened.
+    fn play(mut self) -> Vec<Win> {
+        while self.run_step().is_ok() {}
+        self.timeline
+    }
 
-        'outer2: for col in 0..5 {
-            for row in 0..5 {
-                if !self.state[idx][row*5 + col] {
-                    continue 'outer2
-                }
-            }
-            return true;
-        }
-        false
+    fn is_winning(&self, idx: usize) -> bool {
+        self.won[idx]
     }
 
     fn winning_board(&self) -> Option<usize> {
-        for board_idx in 0..self.boards.len() {
-            if self.is_winning(board_idx) {
-                return Some(board_idx)
-            }
-        }
-        None
+        self.won.iter().position(|&w| w)
     }
 
     fn loosing_board(&self) -> Option<usize> {
-        for board_idx in 0..self.boards.len() {
-            if !self.is_winning(board_idx) {
-                return Some(board_idx)
+        self.won.iter().position(|&w| !w)
+    }
+}
+
+impl repl::Sim for Game {
+    fn step(&mut self) -> Result<(), String> {
+        self.run_step().map_err(String::from)
+    }
+
+    fn show(&self) -> String {
+        let mut out = String::new();
+        if let Some(n) = self.just_called {
+            out += &format!("Just called: {}\n", n);
+        }
+        out += &format!("Numbers left: {}\n", self.nums.len());
+        for idx in 0..self.boards.len() {
+            out += &format!("Board {}{}:\n", idx,
+                            if self.is_winning(idx) { " (winning)" } else { "" });
+            for row in 0..self.size {
+                for col in 0..self.size {
+                    let sq = row*self.size + col;
+                    if self.state[idx][sq] {
+                        out += &format!(" [{:2}]", self.boards[idx][sq]);
+                    } else {
+                        out += &format!("  {:2} ", self.boards[idx][sq]);
+                    }
+                }
+                out += "\n";
             }
         }
-        None
+        out
     }
 
-    fn board_score(&self, idx: usize) -> isize {
-        let mut score = 0;
-        for i in 0..25 {
-            if self.state[idx][i] {
-                continue
-            };
-
-            score += self.boards[idx][i];
+    fn check(&self, cond: &str) -> Result<bool, String> {
+        match cond {
+            "win" => Ok(self.winning_board().is_some()),
+            "all" => Ok(self.loosing_board().is_none()),
+            _ => Err(format!("Unknown condition {}, expected win or all", cond)),
         }
-        score
     }
 }
 
-fn first_part(s: &str) -> isize {
-    let mut g : Game = s.parse().expect("Couldn't parse game");
-
-    let board_score = loop {
-        g.run_step().expect("Failed to run step");
-        match g.winning_board() {
-            Some(idx) => break g.board_score(idx),
-            None => (),
-        };
-    };
-
-    board_score * g.just_called.unwrap()
+fn timeline_csv(timeline: &[Win]) -> String {
+    let mut out = "turn,board,number,pattern,score\n".to_string();
+    for w in timeline {
+        out += &format!("{},{},{},{},{}\n",
+                        w.turn, w.board, w.number, w.pattern, w.score);
+    }
+    out
 }
 
-fn second_part(s: &str) -> isize {
-    let mut g : Game = s.parse().expect("Couldn't parse game");
-
-    let mut idx = 0;
-    let board_score = loop {
-        g.run_step().expect("Failed to run step");
-        match g.loosing_board() {
-            Some(new_idx) => idx = new_idx,
-            None => break g.board_score(idx),
-        };
-    };
+fn first_part(s: &str, rules: &Rules) -> isize {
+    let timeline = rules.parse_game(s).expect("Couldn't parse game").play();
+    timeline.first().expect("No board won").score
+}
 
-    board_score * g.just_called.unwrap()
+fn second_part(s: &str, rules: &Rules) -> isize {
+    let timeline = rules.parse_game(s).expect("Couldn't parse game").play();
+    timeline.last().expect("No board won").score
 }
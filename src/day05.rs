@@ -14,86 +14,637 @@
  * limitations under the License.
  */
 
+use std::env;
+use std::fmt;
 use std::fs;
+use std::io::{self, Write};
 use std::string::String;
-use std::cmp::{Ordering,Ord};
+use std::str::FromStr;
+use std::collections::{BTreeMap, HashMap};
+use std::cmp::max;
+use std::ops::Range;
+use std::time::Instant;
 
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
+
+    let mut args = env::args().skip(1);
+    let arg = args.next();
+    if arg.as_deref() == Some("--bench") {
+        bench();
+        return;
+    }
+    if arg.as_deref() == Some("--heatmap") {
+        let lines = parse(&str).expect("Failed to parse file");
+        let out = heatmap(&lines, &args.collect::<Vec<_>>())
+            .unwrap_or_else(|e| panic!("{}", e));
+        io::stdout().write_all(&out).expect("Couldn't write heatmap");
+        return;
+    }
+    if arg.as_deref() == Some("--overlaps") {
+        let mode = args.next().map_or(Ok(Raster::Diagonal), |m| m.parse())
+            .expect("Usage: day05 --overlaps [axis|diagonal|bresenham|lattice]");
+        let lines = parse(&str).expect("Failed to parse file");
+        let map = mk_map(&lines, mode).unwrap_or_else(|e| panic!("{}", e));
+        for (p, n) in overlaps(&map, 2) {
+            println!("{},{}: {}", p.x, p.y, n);
+        }
+        return;
+    }
 
     println!("{}", first_part(&str));
     println!("{}", second_part(&str));
 }
 
-fn parse_point(s: &str) -> Result<(usize, usize), &'static str> {
-    let idx = s.find(',').ok_or("Couldn't find ','")?;
-    let (before, after) = s.split_at(idx);
-    Ok((before.parse().or(Err("Not a number"))?,
-        after.chars().skip(1)
-        .collect::<String>().parse().or(Err("Not a  number"))?))
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Point {
+    x: isize,
+    y: isize,
 }
 
-fn parse(s: &str) -> Result<Vec<((usize, usize), (usize, usize))>, &'static str> {
-    s.split_terminator('\n').map(|line| {
-        let idx = line.find(" -> ").ok_or("Couldn't find \"->\"")?;
-        let (before, after) = line.split_at(idx);
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.x, self.y)
+    }
+}
 
-        Ok((parse_point(before)?,
-            parse_point(&after.chars().skip(4).collect::<String>())?))   
-    }).collect()
+impl FromStr for Point {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (x, y) = s.trim().split_once(',').ok_or("Couldn't find ','")?;
+        Ok(Point {
+            x: x.parse().or(Err("Not a number"))?,
+            y: y.parse().or(Err("Not a number"))?,
+        })
+    }
 }
 
-fn ord_to_sign(ord: Ordering) -> isize {
-    match ord {
-        Ordering::Less => 1,
-        Ordering::Greater => -1,
-        Ordering::Equal => 0,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Line {
+    start: Point,
+    end: Point,
+}
+
+impl FromStr for Line {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.split_once(" -> ").ok_or("Couldn't find \"->\"")?;
+        Ok(Line { start: start.parse()?, end: end.parse()? })
     }
 }
 
-fn inc(pos: usize, dir: isize) -> usize {
-    ((pos as isize) + dir) as usize
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {}", self.start, self.end)
+    }
 }
 
-fn mk_map(v: &Vec<((usize, usize), (usize, usize))>, diag: bool) -> Vec<Vec<usize>> {
-    let mut r: Vec<Vec<usize>> = Vec::new();
+// How lines are turned into points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Raster {
+    // Only horizontal and vertical lines, anything else is ignored
+    Axis,
+    // Horizontal, vertical and 45° lines, anything else is an error
+    Diagonal,
+    // Any line, approximated with Bresenham's algorithm
+    Bresenham,
+    // Any line, only including points exactly on it
+    Lattice,
+}
 
-    for line in v {
-        let mut x = line.0.0;
-        let mut y = line.0.1;
-        let x_dir = ord_to_sign(x.cmp(&line.1.0));
-        let y_dir = ord_to_sign(y.cmp(&line.1.1));
+impl FromStr for Raster {
+    type Err = &'static str;
 
-        if x_dir != 0 && y_dir != 0 && !diag {
-            continue
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "axis" => Ok(Raster::Axis),
+            "diagonal" => Ok(Raster::Diagonal),
+            "bresenham" => Ok(Raster::Bresenham),
+            "lattice" => Ok(Raster::Lattice),
+            _ => Err("Unknown raster mode"),
         }
+    }
+}
+
+fn gcd(a: isize, b: isize) -> isize {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+impl Line {
+    fn dx(&self) -> isize {
+        self.end.x - self.start.x
+    }
+
+    fn dy(&self) -> isize {
+        self.end.y - self.start.y
+    }
 
-        while x != inc(line.1.0, x_dir) || y != inc(line.1.1, y_dir) {
-            while r.len() <= x {
-                r.push(Vec::new())
+    // Walk from the start in equal steps, including both ends.
+    fn steps(&self, n: isize) -> Vec<Point> {
+        let (sx, sy) = if n == 0 { (0, 0) } else { (self.dx() / n, self.dy() / n) };
+        (0..=n).map(|i| Point {
+            x: self.start.x + sx * i,
+            y: self.start.y + sy * i,
+        }).collect()
+    }
+
+    fn bresenham(&self) -> Vec<Point> {
+        let (dx, dy) = (self.dx().abs(), -self.dy().abs());
+        let (sx, sy) = (self.dx().signum(), self.dy().signum());
+        let mut err = dx + dy;
+        let mut p = self.start;
+        let mut result = vec![p];
+
+        while p != self.end {
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                p.x += sx;
             }
-            while r[x].len() <= y {
-                r[x].push(0)
+            if e2 <= dx {
+                err += dx;
+                p.y += sy;
             }
-            
-            r[x][y] += 1;
-            x = inc(x, x_dir);
-            y = inc(y, y_dir);
+            result.push(p);
+        }
+        result
+    }
+
+    fn rasterise(&self, mode: Raster) -> Result<Vec<Point>, String> {
+        let (dx, dy) = (self.dx(), self.dy());
+        let axis = dx == 0 || dy == 0;
+
+        match mode {
+            Raster::Axis if !axis => Ok(Vec::new()),
+            Raster::Diagonal if !axis && dx.abs() != dy.abs() =>
+                Err(format!("{} isn't horizontal, vertical or 45°", self)),
+            Raster::Axis | Raster::Diagonal => Ok(self.steps(max(dx.abs(), dy.abs()))),
+            Raster::Bresenham => Ok(self.bresenham()),
+            Raster::Lattice => Ok(self.steps(gcd(dx, dy))),
+        }
+    }
+}
+
+fn parse(s: &str) -> Result<Vec<Line>, &'static str> {
+    s.split_terminator('\n').map(|line| line.parse()).collect()
+}
+
+// Number of lines covering each point, only storing points that are
+// covered at least once.
+fn mk_map(v: &[Line], mode: Raster) -> Result<HashMap<Point, usize>, String> {
+    let mut r = HashMap::new();
+
+    for (idx, line) in v.iter().enumerate() {
+        let points = line.rasterise(mode)
+            .map_err(|e| format!("line {}: {}", idx + 1, e))?;
+        for p in points {
+            *r.entry(p).or_insert(0) += 1;
         }
     }
-    r
+    Ok(r)
+}
+
+// Every point covered by at least k lines with its multiplicity,
+// sorted by position.
+fn overlaps(map: &HashMap<Point, usize>, k: usize) -> Vec<(Point, usize)> {
+    let mut result: Vec<_> = map.iter()
+        .filter(|(_, &n)| n >= k)
+        .map(|(&p, &n)| (p, n))
+        .collect();
+    result.sort_unstable();
+    result
+}
+
+// Axis aligned and diagonal lines each lie on a carrier line from one
+// of four families. Points on a carrier are identified by their x
+// coordinate, or their y coordinate for vertical carriers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Family {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    AntiDiagonal,
+}
+
+const FAMILIES: [Family; 4] = [
+    Family::Horizontal, Family::Vertical, Family::Diagonal, Family::AntiDiagonal,
+];
+
+impl Family {
+    fn of(line: &Line) -> Option<Family> {
+        let (dx, dy) = (line.dx(), line.dy());
+        if dy == 0 {
+            Some(Family::Horizontal)
+        } else if dx == 0 {
+            Some(Family::Vertical)
+        } else if dx == dy {
+            Some(Family::Diagonal)
+        } else if dx == -dy {
+            Some(Family::AntiDiagonal)
+        } else {
+            None
+        }
+    }
+
+    // The (carrier, position) of a point in this family.
+    fn locate(self, p: Point) -> (isize, isize) {
+        match self {
+            Family::Horizontal => (p.y, p.x),
+            Family::Vertical => (p.x, p.y),
+            Family::Diagonal => (p.x - p.y, p.x),
+            Family::AntiDiagonal => (p.x + p.y, p.x),
+        }
+    }
+
+    fn point(self, carrier: isize, t: isize) -> Point {
+        match self {
+            Family::Horizontal => Point { x: t, y: carrier },
+            Family::Vertical => Point { x: carrier, y: t },
+            Family::Diagonal => Point { x: t, y: t - carrier },
+            Family::AntiDiagonal => Point { x: t, y: carrier - t },
+        }
+    }
+}
+
+// A maximal range of positions on a carrier covered by the same
+// number of lines.
+#[derive(Clone, Copy, Debug)]
+struct Run {
+    lo: isize,
+    hi: isize,
+    count: usize,
+}
+
+// Sweep over the interval endpoints on a carrier to find the runs.
+fn coverage(mut intervals: Vec<(isize, isize)>) -> Vec<Run> {
+    let mut events = Vec::with_capacity(intervals.len() * 2);
+    for (lo, hi) in intervals.drain(..) {
+        events.push((lo, 1));
+        events.push((hi + 1, -1));
+    }
+    events.sort_unstable();
+
+    let mut runs = Vec::new();
+    let mut count: isize = 0;
+    for (idx, &(pos, delta)) in events.iter().enumerate() {
+        count += delta;
+        match events.get(idx + 1) {
+            Some(&(next, _)) if next > pos && count > 0 =>
+                runs.push(Run { lo: pos, hi: next - 1, count: count as usize }),
+            _ => (),
+        }
+    }
+    runs
+}
+
+fn count_at(runs: &[Run], t: isize) -> usize {
+    let idx = runs.partition_point(|r| r.hi < t);
+    match runs.get(idx) {
+        Some(r) if r.lo <= t => r.count,
+        _ => 0,
+    }
+}
+
+// Count points covered by at least k lines without visiting every
+// covered point. Within a family coverage comes from sweeping each
+// carrier, and points where families cross are found by looking up the
+// carriers crossing each run.
+fn sweep_count(v: &[Line], mode: Raster, k: usize) -> Result<usize, String> {
+    if k == 0 {
+        return Err("Overlap count must be at least 1".to_string());
+    }
+    if mode != Raster::Axis && mode != Raster::Diagonal {
+        return Err("Sweep only supports axis and diagonal modes".to_string());
+    }
+
+    let mut intervals: Vec<BTreeMap<isize, Vec<(isize, isize)>>> =
+        vec![BTreeMap::new(); FAMILIES.len()];
+    for (idx, line) in v.iter().enumerate() {
+        let family = match Family::of(line) {
+            Some(f @ Family::Horizontal) | Some(f @ Family::Vertical) => f,
+            Some(f) if mode == Raster::Diagonal => f,
+            Some(_) => continue,
+            None if mode == Raster::Axis => continue,
+            None => return Err(format!(
+                "line {}: {} isn't horizontal, vertical or 45°", idx + 1, line)),
+        };
+        let (carrier, a) = family.locate(line.start);
+        let (_, b) = family.locate(line.end);
+        intervals[family as usize].entry(carrier).or_default()
+            .push((a.min(b), a.max(b)));
+    }
+
+    let runs: Vec<BTreeMap<isize, Vec<Run>>> = intervals.into_iter().map(|m| {
+        m.into_iter().map(|(carrier, i)| (carrier, coverage(i))).collect()
+    }).collect();
+
+    let mut result = 0;
+    for family_runs in &runs {
+        for run in family_runs.values().flatten() {
+            if run.count >= k {
+                result += (run.hi - run.lo + 1) as usize;
+            }
+        }
+    }
+
+    // Per family coverage of every point on more than one family.
+    let mut crossings: HashMap<Point, [usize; 4]> = HashMap::new();
+    for (fi, &f) in FAMILIES.iter().enumerate() {
+        for (gi, &g) in FAMILIES.iter().enumerate().skip(fi + 1) {
+            for (&carrier, f_runs) in &runs[fi] {
+                for run in f_runs {
+                    // Moving along f changes the carrier in g linearly.
+                    let (v0, _) = g.locate(f.point(carrier, run.lo));
+                    let (v1, _) = g.locate(f.point(carrier, run.lo + 1));
+                    let step = v1 - v0;
+                    let v_end = v0 + step * (run.hi - run.lo);
+
+                    for (&g_carrier, g_runs) in runs[gi].range(v0.min(v_end)..=v0.max(v_end)) {
+                        if (g_carrier - v0) % step != 0 {
+                            continue
+                        }
+                        let p = f.point(carrier, run.lo + (g_carrier - v0) / step);
+                        let g_count = count_at(g_runs, g.locate(p).1);
+                        if g_count > 0 {
+                            let counts = crossings.entry(p).or_insert([0; 4]);
+                            counts[fi] = run.count;
+                            counts[gi] = g_count;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for counts in crossings.values() {
+        for &c in counts {
+            if c >= k {
+                result -= 1;
+            }
+        }
+        if counts.iter().sum::<usize>() >= k {
+            result += 1;
+        }
+    }
+
+    Ok(result)
+}
+
+// Small xorshift generator so benchmarks don't need extra
+// dependencies.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn range(&mut self, lo: isize, hi: isize) -> isize {
+        lo + (self.next() % (hi - lo + 1) as u64) as isize
+    }
+}
+
+fn generate(rng: &mut Rng, count: usize, size: isize, max_len: isize) -> Vec<Line> {
+    (0..count).map(|_| {
+        let start = Point { x: rng.range(0, size), y: rng.range(0, size) };
+        let len = rng.range(0, max_len);
+        let (dx, dy) = match rng.range(0, 3) {
+            0 => (1, 0),
+            1 => (0, 1),
+            2 => (1, 1),
+            _ => (1, -1),
+        };
+        Line { start, end: Point { x: start.x + dx * len, y: start.y + dy * len } }
+    }).collect()
+}
+
+fn bench() {
+    let mut rng = Rng(0x2021_0005);
+    let cases = [
+        (500, 1000, 1000),
+        (500, 100_000, 10_000),
+        (1000, 1_000_000, 50_000),
+    ];
+
+    println!("lines,size,max_len,mk_map_ms,sweep_ms,mk_map_count,sweep_count");
+    for &(count, size, max_len) in &cases {
+        let lines = generate(&mut rng, count, size, max_len);
+
+        let start = Instant::now();
+        let map = mk_map(&lines, Raster::Diagonal).unwrap();
+        let expected = overlaps(&map, 2).len();
+        let map_time = start.elapsed();
+
+        let start = Instant::now();
+        let actual = sweep_count(&lines, Raster::Diagonal, 2).unwrap();
+        let sweep_time = start.elapsed();
+
+        println!("{},{},{},{},{},{},{}", count, size, max_len,
+                 map_time.as_millis(), sweep_time.as_millis(), expected, actual);
+    }
+}
+
+// The region of the map to draw, and the size to scale it to.
+struct View {
+    min: Point,
+    max: Point,
+    width: usize,
+    height: usize,
+}
+
+// Output cells showing offset x, when span source points are drawn
+// across size cells. Shrinking puts each point in one cell, while
+// stretching repeats it over every cell starting within it.
+fn cells(x: usize, span: usize, size: usize) -> Range<usize> {
+    if size <= span {
+        let cell = x * size / span;
+        cell..cell + 1
+    } else {
+        (x * size).div_ceil(span)..((x + 1) * size).div_ceil(span)
+    }
+}
+
+// Sum the coverage of every point falling in each output cell.
+fn density(map: &HashMap<Point, usize>, view: &View) -> Vec<Vec<usize>> {
+    let span_x = (view.max.x - view.min.x + 1) as usize;
+    let span_y = (view.max.y - view.min.y + 1) as usize;
+    let mut result = vec![vec![0; view.width]; view.height];
+
+    for (p, &n) in map {
+        if p.x < view.min.x || p.x > view.max.x || p.y < view.min.y || p.y > view.max.y {
+            continue
+        }
+        for row in cells((p.y - view.min.y) as usize, span_y, view.height) {
+            for col in cells((p.x - view.min.x) as usize, span_x, view.width) {
+                result[row][col] += n;
+            }
+        }
+    }
+    result
+}
+
+fn scale(n: usize, max: usize) -> u8 {
+    (n * 255).checked_div(max).unwrap_or(0) as u8
+}
+
+fn to_pgm(grid: &[Vec<usize>]) -> Vec<u8> {
+    let max = grid.iter().flatten().cloned().max().unwrap_or(0);
+    let mut out = format!("P5\n{} {}\n255\n", grid[0].len(), grid.len()).into_bytes();
+    out.extend(grid.iter().flatten().map(|&n| scale(n, max)));
+    out
+}
+
+// Black through red and yellow to white.
+fn to_ppm(grid: &[Vec<usize>]) -> Vec<u8> {
+    let max = grid.iter().flatten().cloned().max().unwrap_or(0);
+    let mut out = format!("P6\n{} {}\n255\n", grid[0].len(), grid.len()).into_bytes();
+    for &n in grid.iter().flatten() {
+        let v = scale(n, max) as usize * 3;
+        out.extend([v.min(255), v.clamp(255, 510) - 255, v.clamp(510, 765) - 510]
+                   .iter().map(|&c| c as u8));
+    }
+    out
+}
+
+fn to_ascii(grid: &[Vec<usize>]) -> String {
+    let ramp: Vec<char> = " .:-=+*#%@".chars().collect();
+    let max = grid.iter().flatten().cloned().max().unwrap_or(0);
+    let steps = ramp.len() - 1;
+    let level = |n: usize| if n == 0 { 0 } else { 1 + (n - 1) * steps / max };
+
+    let mut out = String::new();
+    for row in grid {
+        out.extend(row.iter().map(|&n| ramp[level(n)]));
+        out.push('\n');
+    }
+
+    out += "\nLegend (coverage per cell):\n";
+    out += "  ' ' 0\n";
+    for (idx, c) in ramp.iter().enumerate().skip(1) {
+        let lo = ((idx - 1) * max).div_ceil(steps) + 1;
+        let hi = (idx * max).div_ceil(steps);
+        if lo <= hi {
+            out += &format!("  '{}' {}-{}\n", c, lo, hi);
+        }
+    }
+    out
+}
+
+fn parse_size(s: &str) -> Result<(usize, usize), String> {
+    let (w, h) = s.split_once('x').ok_or(format!("Size should be WxH, got {}", s))?;
+    match (w.parse(), h.parse()) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(format!("Invalid size {}", s)),
+    }
+}
+
+fn parse_crop(s: &str) -> Result<(Point, Point), String> {
+    let nums = s.split(',').map(|n| n.parse())
+        .collect::<Result<Vec<isize>, _>>()
+        .or(Err(format!("Invalid crop {}", s)))?;
+    match nums[..] {
+        [x0, y0, x1, y1] if x0 <= x1 && y0 <= y1 =>
+            Ok((Point { x: x0, y: y0 }, Point { x: x1, y: y1 })),
+        _ => Err(format!("Crop should be x0,y0,x1,y1, got {}", s)),
+    }
+}
+
+// Arguments are the format (pgm, ppm or ascii) followed by any of
+// --raster <mode>, --crop x0,y0,x1,y1 and --size WxH. By default the
+// whole map is drawn, shrunk to fit 1000x1000 for images or 80x40 for
+// text.
+fn heatmap(lines: &[Line], args: &[String]) -> Result<Vec<u8>, String> {
+    let format = args.first().ok_or("Usage: day05 --heatmap <pgm|ppm|ascii> \
+                                     [--raster <mode>] [--crop x0,y0,x1,y1] [--size WxH]")?;
+    let mut mode = Raster::Diagonal;
+    let mut crop = None;
+    let mut size = None;
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        let val = rest.next().ok_or(format!("Missing value for {}", arg))?;
+        match arg.as_str() {
+            "--raster" => mode = val.parse()?,
+            "--crop" => crop = Some(parse_crop(val)?),
+            "--size" => size = Some(parse_size(val)?),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+
+    let map = mk_map(lines, mode)?;
+    let (min, max) = match crop {
+        Some(c) => c,
+        None => (
+            Point { x: map.keys().map(|p| p.x).min().unwrap_or(0),
+                    y: map.keys().map(|p| p.y).min().unwrap_or(0) },
+            Point { x: map.keys().map(|p| p.x).max().unwrap_or(0),
+                    y: map.keys().map(|p| p.y).max().unwrap_or(0) },
+        ),
+    };
+    let limit = if format == "ascii" { (80, 40) } else { (1000, 1000) };
+    let (width, height) = size.unwrap_or((
+        ((max.x - min.x + 1) as usize).min(limit.0),
+        ((max.y - min.y + 1) as usize).min(limit.1),
+    ));
+
+    let grid = density(&map, &View { min, max, width, height });
+    match format.as_str() {
+        "pgm" => Ok(to_pgm(&grid)),
+        "ppm" => Ok(to_ppm(&grid)),
+        "ascii" => Ok(to_ascii(&grid).into_bytes()),
+        _ => Err(format!("Unknown heatmap format {}", format)),
+    }
 }
 
 fn first_part(s: &str) -> usize {
     let lines = parse(s).expect("Failed to parse file");
 
-    mk_map(&lines, false).iter().flatten().filter(|i| **i >= 2).count()
+    sweep_count(&lines, Raster::Axis, 2).unwrap()
 }
 
 fn second_part(s: &str) -> usize {
     let lines = parse(s).expect("Failed to parse file");
 
-    mk_map(&lines, true).iter().flatten().filter(|i| **i >= 2).count()
+    sweep_count(&lines, Raster::Diagonal, 2).unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Small random maps, shifted to include negative coordinates and
+    // with some lines reversed. Zero length lines come from generate.
+    #[test]
+    fn sweep_matches_mk_map() {
+        let mut rng = Rng(0x2021_0005);
+        for _ in 0..50 {
+            let lines: Vec<Line> = generate(&mut rng, 30, 20, 8).into_iter().map(|l| {
+                let shift = |p: Point| Point { x: p.x - 10, y: p.y - 10 };
+                if rng.next().is_multiple_of(2) {
+                    Line { start: shift(l.end), end: shift(l.start) }
+                } else {
+                    Line { start: shift(l.start), end: shift(l.end) }
+                }
+            }).collect();
+
+            for mode in [Raster::Axis, Raster::Diagonal] {
+                let map = mk_map(&lines, mode).unwrap();
+                for k in 1..=4 {
+                    assert_eq!(sweep_count(&lines, mode, k).unwrap(),
+                               overlaps(&map, k).len(), "{:?} k = {}", mode, k);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sweep_rejects_zero_overlaps() {
+        assert!(sweep_count(&[], Raster::Axis, 0).is_err());
+    }
 }
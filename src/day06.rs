@@ -14,44 +14,261 @@
  * limitations under the License.
  */
 
+use std::env;
 use std::fs;
 use std::string::String;
+use std::cmp::max;
+use std::convert::TryFrom;
+use num_bigint::BigUint;
 
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
+
+    let mut model = Model::default();
+    let mut days = None;
+    let mut csv = None;
+    let mut modulus = None;
+    let mut args = env::args().skip(1);
+    let usage = "Usage: day06 [--days <n>] [--mod <p>] [--csv <days>] \
+                 [--reset <n>] [--newborn <n>] [--lifespan <n>], \
+                 where p is at least 2 and the lifespan at least 1";
+    while let Some(arg) = args.next() {
+        let val: BigUint = args.next().and_then(|v| v.parse().ok()).expect(usage);
+        let small = || usize::try_from(&val).expect("Value too large");
+        match arg.as_str() {
+            "--days" => days = Some(val),
+            "--mod" => {
+                assert!(val >= BigUint::from(2u32), "{}", usage);
+                modulus = Some(val);
+            },
+            "--csv" => csv = Some(small()),
+            "--reset" => model.reset = small(),
+            "--newborn" => model.newborn = small(),
+            "--lifespan" => {
+                assert!(small() >= 1, "{}", usage);
+                model.lifespan = Some(small());
+            },
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
+
+    let initial = parse(&str, &model);
+    if let Some(days) = csv {
+        print!("{}", to_csv(&model, initial, days));
+        return;
+    }
+    if let Some(days) = days {
+        println!("{}", population(&model, &initial, &days, modulus.as_ref()));
+        return;
+    }
 
     println!("{}", first_part(&str));
     println!("{}", second_part(&str));
 }
 
-fn parse(s: &str) -> [usize; 9] {
-    let mut r = [0; 9];
+// A fish with timer 0 resets its timer to `reset` and spawns a new
+// fish with timer `newborn`. With a lifespan, fish die once they are
+// that many days old, counting the initial fish as newborn.
+#[derive(Clone, Copy, Debug)]
+struct Model {
+    reset: usize,
+    newborn: usize,
+    lifespan: Option<usize>,
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        Model { reset: 6, newborn: 8, lifespan: None }
+    }
+}
+
+impl Model {
+    fn timers(&self) -> usize {
+        max(self.reset, self.newborn) + 1
+    }
+
+    // Ages are only tracked when there is a lifespan.
+    fn ages(&self) -> usize {
+        self.lifespan.unwrap_or(1)
+    }
+
+    fn states(&self) -> usize {
+        self.timers() * self.ages()
+    }
+
+    // States are numbered by age, then timer.
+    fn state(&self, age: usize, timer: usize) -> usize {
+        age * self.timers() + timer
+    }
+
+    // The states a fish in the given state produces the next day.
+    fn next(&self, state: usize) -> Vec<usize> {
+        let (age, timer) = (state / self.timers(), state % self.timers());
+        let age = match self.lifespan {
+            Some(l) if age + 1 >= l => return Vec::new(),
+            Some(_) => age + 1,
+            None => 0,
+        };
+
+        if timer == 0 {
+            vec![self.state(age, self.reset), self.state(0, self.newborn)]
+        } else {
+            vec![self.state(age, timer - 1)]
+        }
+    }
+}
+
+fn parse(s: &str, model: &Model) -> Vec<BigUint> {
+    let mut r = vec![BigUint::from(0u32); model.states()];
     for n in s.split(',').map(|n| -> usize {
         n.trim().parse().expect("Failed to parse")
     }) {
-        r[n] += 1;
+        assert!(n < model.timers(), "Timer {} is out of range for the model", n);
+        r[model.state(0, n)] += 1u32;
+    }
+    r
+}
+
+fn step(model: &Model, v: &[BigUint]) -> Vec<BigUint> {
+    let mut r = vec![BigUint::from(0u32); v.len()];
+    for (state, n) in v.iter().enumerate() {
+        for next in model.next(state) {
+            r[next] += n;
+        }
+    }
+    r
+}
+
+type Matrix = Vec<Vec<BigUint>>;
+
+// One day as a matrix acting on the count of fish in each state.
+fn transition(model: &Model) -> Matrix {
+    let n = model.states();
+    let mut m = vec![vec![BigUint::from(0u32); n]; n];
+    for (src, dests) in (0..n).map(|src| model.next(src)).enumerate() {
+        for dest in dests {
+            m[dest][src] += 1u32;
+        }
+    }
+    m
+}
+
+fn mat_mul(a: &Matrix, b: &Matrix, modulus: Option<&BigUint>) -> Matrix {
+    let n = a.len();
+    let mut r = vec![vec![BigUint::from(0u32); n]; n];
+    for (i, row) in r.iter_mut().enumerate() {
+        for (j, val) in row.iter_mut().enumerate() {
+            for k in 0..n {
+                *val += &a[i][k] * &b[k][j];
+            }
+            if let Some(p) = modulus {
+                *val %= p;
+            }
+        }
     }
     r
 }
 
-fn iterate(v: [usize; 9]) -> [usize; 9] {
-    [v[1], v[2], v[3], v[4], v[5], v[6], v[7]+v[0], v[8], v[0]]
+// Raise m to the given power by repeated squaring, reducing modulo
+// the modulus if there is one.
+fn mat_pow(m: &Matrix, exp: &BigUint, modulus: Option<&BigUint>) -> Matrix {
+    let n = m.len();
+    let mut result: Matrix = (0..n).map(|i| {
+        (0..n).map(|j| BigUint::from((i == j) as u32)).collect()
+    }).collect();
+    let mut base = m.clone();
+
+    for bit in 0..exp.bits() {
+        if exp.bit(bit) {
+            result = mat_mul(&result, &base, modulus);
+        }
+        base = mat_mul(&base, &base, modulus);
+    }
+    result
 }
 
-fn first_part(s: &str) -> usize {
-    let mut s = parse(s);
-    for _ in 0..80 {
-        s = iterate(s);
+// Total number of fish after the given number of days, optionally
+// modulo some number. With a lifespan there are too many states for
+// the matrix to pay off, so the days are stepped through one by one,
+// as they are when there are only a few days.
+fn population(model: &Model, v: &[BigUint], days: &BigUint,
+              modulus: Option<&BigUint>) -> BigUint {
+    let states = BigUint::from(model.states());
+    if model.lifespan.is_some() || *days <= &states * &states {
+        let days = usize::try_from(days).expect("Too many days to step through");
+        let mut v = v.to_vec();
+        for _ in 0..days {
+            v = step(model, &v);
+            if let Some(p) = modulus {
+                v.iter_mut().for_each(|n| *n %= p);
+            }
+        }
+        let total = v.into_iter().sum::<BigUint>();
+        return match modulus {
+            Some(p) => total % p,
+            None => total,
+        };
+    }
+
+    let m = mat_pow(&transition(model), days, modulus);
+    let mut total = BigUint::from(0u32);
+    for row in &m {
+        for (a, b) in row.iter().zip(v.iter()) {
+            total += a * b;
+        }
+    }
+    match modulus {
+        Some(p) => total % p,
+        None => total,
     }
-    s.iter().fold(0, |x,y| x+y)
 }
 
-fn second_part(s: &str) -> usize {
-    let mut s = parse(s);
-    for _ in 0..256 {
-        s = iterate(s);
+// The total and the number of fish with each timer value, and each
+// age if there is a lifespan, for every day up to the given one.
+fn to_csv(model: &Model, mut v: Vec<BigUint>, days: usize) -> String {
+    let mut out = "day,total".to_string();
+    for timer in 0..model.timers() {
+        out += &format!(",timer_{}", timer);
+    }
+    if model.lifespan.is_some() {
+        for age in 0..model.ages() {
+            out += &format!(",age_{}", age);
+        }
+    }
+    out += "\n";
+
+    for day in 0..=days {
+        let mut timers = vec![BigUint::from(0u32); model.timers()];
+        let mut ages = vec![BigUint::from(0u32); model.ages()];
+        for (state, n) in v.iter().enumerate() {
+            timers[state % model.timers()] += n;
+            ages[state / model.timers()] += n;
+        }
+
+        out += &format!("{},{}", day, v.iter().sum::<BigUint>());
+        for n in &timers {
+            out += &format!(",{}", n);
+        }
+        if model.lifespan.is_some() {
+            for n in &ages {
+                out += &format!(",{}", n);
+            }
+        }
+        out += "\n";
+
+        v = step(model, &v);
     }
-    s.iter().fold(0, |x,y| x+y)
+    out
+}
+
+fn first_part(s: &str) -> BigUint {
+    let model = Model::default();
+    population(&model, &parse(s, &model), &BigUint::from(80u32), None)
+}
+
+fn second_part(s: &str) -> BigUint {
+    let model = Model::default();
+    population(&model, &parse(s, &model), &BigUint::from(256u32), None)
 }
@@ -14,57 +14,223 @@
  * limitations under the License.
  */
 
+use std::env;
 use std::fs;
 use std::string::String;
+use std::collections::BTreeMap;
+use num_bigint::BigUint;
 
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
 
-    println!("{}", first_part(&str));
-    println!("{}", second_part(&str));
+    let args: Vec<String> = env::args().skip(1).collect();
+    let usage = "Usage: day07 [--report <cost> | --curve <cost> <lo> <hi>], \
+                 where cost is linear, triangular or a power k >= 1";
+    match args.first().map(|a| a.as_str()) {
+        Some("--report") => {
+            let f = cost_fn(args.get(1).expect(usage)).expect(usage);
+            print!("{}", report(&parse(&str), &*f));
+        },
+        Some("--curve") => {
+            let f = cost_fn(args.get(1).expect(usage)).expect(usage);
+            let lo = args.get(2).and_then(|n| n.parse().ok()).expect(usage);
+            let hi = args.get(3).and_then(|n| n.parse().ok()).expect(usage);
+            let swarm = parse(&str);
+            print!("{}", curve(&group(&swarm.crabs), swarm.scale, lo, hi, &*f));
+        },
+        Some(_) => panic!("{}", usage),
+        None => {
+            println!("{}", first_part(&str));
+            println!("{}", second_part(&str));
+        },
+    }
 }
 
-fn parse(s: &str) -> Vec<usize> {
-    let mut result = Vec::new();
-    for n in s.split(',') {
-        let n = n.trim().parse().expect("Couldn't parse value");
+// A crab uses multiplier times as much fuel as a normal crab for the
+// same move. Multipliers can be decimals, so they're stored as whole
+// numbers of 10^-scale, where scale is the most decimal places any
+// multiplier has. That keeps all the fuel sums exact.
+#[derive(Clone, Copy, Debug)]
+struct Crab {
+    pos: isize,
+    multiplier: u128,
+}
 
-        while result.len() <= n {
-            result.push(0);
-        }
+struct Swarm {
+    crabs: Vec<Crab>,
+    scale: u32,
+}
+
+// Returns the digits of a positive decimal as an integer, and the
+// number of decimal places.
+fn parse_decimal(s: &str) -> Option<(u128, u32)> {
+    let (whole, frac) = s.split_once('.').unwrap_or((s, ""));
+    if whole.is_empty() && frac.is_empty() ||
+        !whole.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let digits = format!("{}{}", whole, frac).parse().ok()?;
+    Some((digits, frac.len() as u32)).filter(|&(d, _)| d > 0)
+}
+
+// Each entry is either a position, or a position and fuel multiplier
+// written as `pos*multiplier`, e.g. `16*1.5`. Several crabs at the
+// same position are written as separate entries.
+fn parse(s: &str) -> Swarm {
+    let entries: Vec<(isize, u128, u32)> = s.split(',').map(|n| {
+        let (pos, mul) = n.trim().split_once('*').unwrap_or((n.trim(), "1"));
+        let (digits, places) = parse_decimal(mul)
+            .expect("Couldn't parse multiplier, expected a positive decimal");
+        (pos.parse().expect("Couldn't parse value"), digits, places)
+    }).collect();
+
+    let scale = entries.iter().map(|&(_, _, places)| places).max().unwrap_or(0);
+    let crabs = entries.into_iter().map(|(pos, digits, places)| {
+        let multiplier = 10u128.checked_pow(scale - places)
+            .and_then(|unit| digits.checked_mul(unit))
+            .expect("Multipliers have too many decimal places");
+        Crab { pos, multiplier }
+    }).collect();
+    Swarm { crabs, scale }
+}
+
+// Format an amount of fuel counted in units of 10^-scale.
+fn fuel(n: &BigUint, scale: u32) -> String {
+    if scale == 0 {
+        return n.to_string();
+    }
+    let unit = BigUint::from(10u32).pow(scale);
+    let frac = format!("{:0width$}", n % &unit, width = scale as usize);
+    match frac.trim_end_matches('0') {
+        "" => (n / unit).to_string(),
+        frac => format!("{}.{}", n / unit, frac),
+    }
+}
 
-        result[n] += 1;
+// Crab positions with the total multiplier of the crabs at each,
+// sorted by position.
+fn group(crabs: &[Crab]) -> Vec<(isize, u128)> {
+    let mut result = BTreeMap::new();
+    for c in crabs {
+        *result.entry(c.pos).or_insert(0) += c.multiplier;
     }
 
-    result
+    result.into_iter().collect()
 }
 
-fn find_best<T: Fn(usize) -> usize>(v: &Vec<usize>, f: T) -> usize {
-    let mut best_cost = usize::MAX;
-    for guess in 0..v.len() {
-        let cost = v.iter().enumerate()
-            .map(|(i,x)| {
-                let dist = (guess as isize - i as isize).abs() as usize;
-                f(dist) * x
-            })
-            .fold(0, |x,y| x+y);
+// Costs are BigUints as high powers of a distance soon overflow.
+fn cost_fn(spec: &str) -> Option<Box<dyn Fn(u128) -> BigUint>> {
+    match spec {
+        "linear" => Some(Box::new(linear)),
+        "triangular" => Some(Box::new(triangular)),
+        _ => {
+            let k: u32 = spec.parse().ok().filter(|&k| k >= 1)?;
+            Some(Box::new(move |dist: u128| BigUint::from(dist).pow(k)))
+        },
+    }
+}
+
+fn cost<T>(v: &[(isize, u128)], guess: isize, f: &T) -> BigUint
+    where T: Fn(u128) -> BigUint + ?Sized {
+    v.iter().map(|&(pos, n)| f(guess.abs_diff(pos) as u128) * n).sum()
+}
 
-        if cost < best_cost {
-            best_cost = cost;
+// For a convex cost function the total cost is convex in the guess,
+// so the optimal positions form a range. Binary search for where the
+// cost stops decreasing and where it starts increasing again,
+// returning both ends of the range and the cost.
+fn optima<T>(v: &[(isize, u128)], f: &T) -> (isize, isize, BigUint)
+    where T: Fn(u128) -> BigUint + ?Sized {
+    let search = |stop: &dyn Fn(&BigUint, &BigUint) -> bool| {
+        let mut lo = v.first().expect("No crabs").0;
+        let mut hi = v.last().unwrap().0;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if stop(&cost(v, mid, f), &cost(v, mid + 1, f)) {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        lo
+    };
+
+    let first = search(&|here, next| here <= next);
+    let last = search(&|here, next| here < next);
+    (first, last, cost(v, first, f))
+}
+
+// With a linear cost any weighted median is optimal.
+fn median(v: &[(isize, u128)]) -> isize {
+    let total: u128 = v.iter().map(|&(_, n)| n).sum();
+    let mut seen = 0;
+    for &(pos, n) in v {
+        seen += n;
+        if seen * 2 >= total {
+            return pos;
         }
     }
+    panic!("No crabs")
+}
+
+// With a triangular cost the optimum is within half a step of the
+// weighted mean, so it's enough to check the integers either side.
+fn near_mean(v: &[(isize, u128)]) -> Vec<isize> {
+    let total: i128 = v.iter().map(|&(_, n)| n as i128).sum();
+    let sum: i128 = v.iter().map(|&(pos, n)| pos as i128 * n as i128).sum();
+    let floor = sum.div_euclid(total) as isize;
+    vec![floor, floor + 1]
+}
+
+fn linear(dist: u128) -> BigUint {
+    BigUint::from(dist)
+}
 
-    best_cost
+fn triangular(dist: u128) -> BigUint {
+    BigUint::from(dist)*(dist+1)/2u32
+}
+
+fn report<T>(swarm: &Swarm, f: &T) -> String
+    where T: Fn(u128) -> BigUint + ?Sized {
+    let (first, last, best) = optima(&group(&swarm.crabs), f);
+
+    let mut out = if first == last {
+        format!("Optimal position: {}\n", first)
+    } else {
+        format!("Optimal positions: {} to {}\n", first, last)
+    };
+    out += &format!("Total fuel: {}\n", fuel(&best, swarm.scale));
+
+    out += "crab,position,multiplier,fuel\n";
+    for (idx, c) in swarm.crabs.iter().enumerate() {
+        let used = f(first.abs_diff(c.pos) as u128) * c.multiplier;
+        out += &format!("{},{},{},{}\n", idx, c.pos,
+                        fuel(&c.multiplier.into(), swarm.scale), fuel(&used, swarm.scale));
+    }
+    out
+}
+
+fn curve<T>(v: &[(isize, u128)], scale: u32, lo: isize, hi: isize, f: &T) -> String
+    where T: Fn(u128) -> BigUint + ?Sized {
+    let mut out = "position,cost\n".to_string();
+    for guess in lo..=hi {
+        out += &format!("{},{}\n", guess, fuel(&cost(v, guess, f), scale));
+    }
+    out
 }
 
-fn first_part(s: &str) -> usize {
-    let v = parse(s);
-    find_best(&v, |dist| dist)
+fn first_part(s: &str) -> String {
+    let swarm = parse(s);
+    let v = group(&swarm.crabs);
+    fuel(&cost(&v, median(&v), &linear), swarm.scale)
 }
 
-fn second_part(s: &str) -> usize {
-    let v = parse(s);
-    find_best(&v, |dist| dist*(dist+1)/2)
+fn second_part(s: &str) -> String {
+    let swarm = parse(s);
+    let v = group(&swarm.crabs);
+    let best = near_mean(&v).into_iter().map(|guess| cost(&v, guess, &triangular))
+        .min().unwrap();
+    fuel(&best, swarm.scale)
 }
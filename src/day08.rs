@@ -14,221 +14,354 @@
  * limitations under the License.
  */
 
+use std::env;
 use std::fs;
 use std::string::String;
-use std::str::FromStr;
-use std::convert::TryInto;
 
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
 
-    println!("{}", first_part(&str));
-    println!("{}", second_part(&str));
-}
+    let mut encoding = None;
+    let mut command = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--encoding" => {
+                let file = args.next().expect("Usage: day08 --encoding <file>");
+                let text = fs::read_to_string(file).expect("Couldn't read encoding");
+                encoding = Some(Encoding::parse(&text).unwrap_or_else(|e| panic!("{}", e)));
+            },
+            "--candidates" | "--decode" => command = Some(arg),
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
 
-struct Display {
-    examples: [[bool; 7]; 10],
-    display: [[bool; 7]; 4],
+    match command.as_deref() {
+        Some("--candidates") => print!("{}", candidates(
+            &str, &encoding.unwrap_or_else(Encoding::seven_segment))),
+        Some("--decode") => print!("{}", decode(
+            &str, &encoding.unwrap_or_else(Encoding::seven_segment))),
+        _ => {
+            assert!(encoding.is_none(), "The puzzle parts only use the seven segment \
+                                         encoding, use --encoding with --decode or --candidates");
+            println!("{}", first_part(&str));
+            println!("{}", second_part(&str));
+        },
+    }
 }
 
-impl FromStr for Display {
-    type Err = &'static str;
+// A set of patterns, one bit per segment or wire.
+type Mask = u64;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (ex, curr) = s.split_once('|').ok_or("Couldn't find '|'")?;
+// Segments and wires are both named by letters starting at 'a', so
+// there can be at most 26 of them.
+const MAX_WIDTH: usize = 26;
 
-        let mut examples = Vec::new();
-        for s in ex.split_whitespace() {
-            let mut r = Vec::new();
-            for i in 0..7 {
-                let c = char::from_u32('a' as u32 + i).unwrap();
-                r.push(s.contains(c));
-            }
-            let r = r.try_into().unwrap();
-            examples.push(r);
+// The segments lit for each glyph a display can show.
+struct Encoding {
+    width: usize,
+    glyphs: Vec<(String, Mask)>,
+}
+
+fn parse_pattern(s: &str, width: usize) -> Result<Mask, String> {
+    let mut r = 0;
+    for c in s.chars() {
+        let bit = (c as usize).wrapping_sub('a' as usize);
+        if bit >= width {
+            return Err(format!("Pattern {:?} uses a segment outside a-{}",
+                               s, (b'a' + width as u8 - 1) as char));
         }
-        let examples = examples.try_into().or(
-            Err("Didn't find 10 examples!"))?;
-
-        let mut display = Vec::new();
-        for s in curr.split_whitespace() {
-            let mut r = Vec::new();
-            for i in 0..7 {
-                let c = char::from_u32('a' as u32 + i).unwrap();
-                r.push(s.contains(c));
-            }
-            let r = r.try_into().unwrap();
-            display.push(r);
+        r |= 1 << bit;
+    }
+    Ok(r)
+}
+
+impl Encoding {
+    fn seven_segment() -> Self {
+        let digits = [
+            "abcefg", "cf", "acdeg", "acdfg", "bcdf",
+            "abdfg", "abdefg", "acf", "abcdefg", "abcdfg",
+        ];
+        Encoding {
+            width: 7,
+            glyphs: digits.iter().enumerate().map(|(n, d)| {
+                (n.to_string(), parse_pattern(d, 7).unwrap())
+            }).collect(),
+        }
+    }
+
+    // One glyph per line as `<name> <segments>`, e.g. `7 acf`. The
+    // width is the highest segment used unless given with a
+    // `width <n>` line. Blank lines and lines starting with '#' are
+    // ignored.
+    fn parse(s: &str) -> Result<Self, String> {
+        let lines: Vec<(&str, &str)> = s.lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| l.split_once(' ').ok_or(format!("Expected `<name> <segments>`, got {:?}", l)))
+            .collect::<Result<_, _>>()?;
+
+        let width = match lines.iter().find(|(name, _)| *name == "width") {
+            Some((_, w)) => w.trim().parse().or(Err(format!("Invalid width {}", w)))?,
+            None => lines.iter().flat_map(|(_, segs)| segs.chars())
+                .filter(|c| c.is_ascii_lowercase())
+                .map(|c| c as usize - 'a' as usize + 1).max().unwrap_or(0),
+        };
+        if width == 0 || width > MAX_WIDTH {
+            return Err(format!("Width must be between 1 and {}", MAX_WIDTH));
         }
-        let display = display.try_into().or(
-            Err("Didn't find 4 digits!"))?;
+
+        let glyphs = lines.iter().filter(|(name, _)| *name != "width")
+            .map(|(name, segs)| Ok((name.to_string(), parse_pattern(segs.trim(), width)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Encoding { width, glyphs })
+    }
+
+    fn show(&self, reading: &[usize]) -> String {
+        reading.iter().map(|&g| self.glyphs[g].0.as_str()).collect()
+    }
+}
+
+// Examples can be any subset of the glyphs, the wiring is solved from
+// the examples and the displayed glyphs together.
+struct Display {
+    examples: Vec<Mask>,
+    display: Vec<Mask>,
+}
+
+impl Display {
+    fn parse(s: &str, enc: &Encoding) -> Result<Self, String> {
+        let (ex, curr) = s.split_once('|').ok_or("Couldn't find '|'")?;
+
+        let examples = ex.split_whitespace().map(|p| parse_pattern(p, enc.width))
+            .collect::<Result<_, _>>()?;
+        let display = curr.split_whitespace().map(|p| parse_pattern(p, enc.width))
+            .collect::<Result<_, _>>()?;
 
         Ok(Display {examples, display})
     }
 }
 
+// Mapping goes from wire -> segment
+type Mapping = Vec<usize>;
+
 impl Display {
-    fn exclude(dis1: &[bool; 7], dis2: &[bool; 7]) -> [bool; 7] {
-        dis1.iter().zip(dis2.iter()).map(|(a,b)| *a && !b)
-            .collect::<Vec<bool>>().try_into().unwrap()
-    }
-
-    fn count(dis: &[bool; 7]) -> usize {
-        dis.iter().fold(0, |x,y| x+(*y as usize))
-    }
-
-    // Mapping goes from wire -> segment
-    fn solve(&self) -> [usize; 7] {
-        let two_segment = self.examples.iter()
-            .find(|&x| Display::count(x) == 2).unwrap();
-        let three_segment = self.examples.iter()
-            .find(|&x| Display::count(x) == 3).unwrap();
-        let four_segment = self.examples.iter()
-            .find(|&x| Display::count(x) == 4).unwrap();
-
-        // Find the (cf) segment pair, as the two segments lit up
-        // on the 2-segment display. We don't yet know which is which
-        // though.
-        let mut iter = two_segment.iter()
-            .enumerate().filter(|(_,&val)| val);
-        let cf_segments = (
-            iter.next().unwrap().0,
-            iter.next().unwrap().0
-        );
-
-        // Find the 'a' segment. This is the segment lit up on the only
-        // 3-segment display, that is not lit on the only 2-segment
-        // display.
-        let a_segment = Display::exclude(three_segment, two_segment)
-            .iter().enumerate().find(|(_,&val)| val).unwrap().0;
-
-        // Find the (bd) segment pair, as the two segments lit up on
-        // the 4-segment display but not the 2-segment display.
-        let diff = Display::exclude(four_segment, two_segment);
-        let mut iter = diff.iter().enumerate().filter(|(_,&val)| val);
-        let bd_segments = (
-            iter.next().unwrap().0,
-            iter.next().unwrap().0
-        );
-
-        // Find the (eg) segment as the remaining two segments.
-        let mut iter = (0..7).filter(|&i| {
-            i != a_segment &&
-                i != cf_segments.0 &&
-                i != cf_segments.1 &&
-                i != bd_segments.0 &&
-                i != bd_segments.1
-        });
-        let eg_segments = (
-            iter.next().unwrap(),
-            iter.next().unwrap()
-        );
-
-        // For two of these pairs, one element is lit on all three
-        // 5-segment displays, one element is lit for only 1.
-        let check_single = |idx| {
-            self.examples.iter()
-                .filter(|&x| x[idx] && Display::count(x) == 5).count() == 1
-        };
+    fn patterns(&self) -> impl Iterator<Item=&Mask> {
+        self.examples.iter().chain(self.display.iter())
+    }
 
-        let (b_segment, d_segment);
-        if check_single(bd_segments.0) {
-            b_segment = bd_segments.0;
-            d_segment = bd_segments.1;
-        } else {
-            b_segment = bd_segments.1;
-            d_segment = bd_segments.0;
-        }
+    // Could the partial mapping still turn every pattern into a glyph?
+    fn consistent(&self, enc: &Encoding, mapping: &[Option<usize>]) -> bool {
+        self.patterns().all(|&p| {
+            enc.glyphs.iter().any(|&(_, g)| {
+                g.count_ones() == p.count_ones() &&
+                    mapping.iter().enumerate().all(|(wire, seg)| {
+                        seg.is_none_or(|s| (g >> s & 1) == (p >> wire & 1))
+                    })
+            })
+        })
+    }
 
-        let (e_segment, g_segment);
-        if check_single(eg_segments.0) {
-            e_segment = eg_segments.0;
-            g_segment = eg_segments.1;
-        } else {
-            e_segment = eg_segments.1;
-            g_segment = eg_segments.0;
+    // Calls visit with each complete mapping, stopping early if it
+    // returns false. Returns whether the search ran to the end.
+    fn search(&self, enc: &Encoding, wire: usize, mapping: &mut Vec<Option<usize>>,
+              visit: &mut dyn FnMut(&[usize]) -> bool) -> bool {
+        if wire == enc.width {
+            let complete: Mapping = mapping.iter().map(|s| s.unwrap()).collect();
+            return visit(&complete);
+        }
+        for seg in 0..enc.width {
+            if mapping.contains(&Some(seg)) {
+                continue
+            }
+            mapping[wire] = Some(seg);
+            let done = !self.consistent(enc, mapping) ||
+                self.search(enc, wire + 1, mapping, visit);
+            mapping[wire] = None;
+            if !done {
+                return false
+            }
         }
+        true
+    }
+
+    // Visit every mapping that turns all the patterns into glyphs,
+    // found by assigning wires one at a time and backtracking as soon
+    // as some pattern can't match any glyph.
+    fn solve(&self, enc: &Encoding, visit: &mut dyn FnMut(&[usize]) -> bool) {
+        self.search(enc, 0, &mut vec![None; enc.width], visit);
+    }
 
-        // For (cf) we use a different trick. Wire 'f' is lit on all
-        // 6-segment displays, while 'c' is only lit on 2 of them.
-        let (c_segment, f_segment);
-        if self.examples.iter()
-            .filter(|&x| x[cf_segments.0] &&
-                    Display::count(x) == 6).count() == 2 {
-                c_segment = cf_segments.0;
-                f_segment = cf_segments.1;
-            } else {
-                c_segment = cf_segments.1;
-                f_segment = cf_segments.0;
+    // Indexes of the displayed glyphs under the given mapping.
+    fn map(&self, enc: &Encoding, map: &[usize]) -> Vec<usize> {
+        self.display.iter().map(|&p| {
+            let mut segments = 0;
+            for (wire, &seg) in map.iter().enumerate() {
+                segments |= (p >> wire & 1) << seg;
             }
+            enc.glyphs.iter().position(|&(_, g)| g == segments).unwrap()
+        }).collect()
+    }
 
-        [a_segment, b_segment, c_segment, d_segment,
-         e_segment, f_segment, g_segment]
-    }
-
-    fn map(&self, map: [usize; 7]) -> [usize; 4] {
-        let mut result = Vec::new();
-
-        for i in 0..4 {
-            let count = self.display[i].iter().fold(0, |x,y| x+(*y as usize));
-            result.push(match count {
-                2 => 1,
-                3 => 7,
-                4 => 4,
-                5 => {
-                    if self.display[i][map[1]] {
-                        // wire b is set
-                        5
-                    } else if self.display[i][map[4]] {
-                        // wire e is set
-                        2
-                    } else {
-                        3
-                    }
-                },
-                6 => {
-                    if !self.display[i][map[3]] {
-                        // wire d is unset
-                        0
-                    } else if !self.display[i][map[2]] {
-                        // wire c is unset
-                        6
-                    } else if !self.display[i][map[4]] {
-                        // wire 4 is unset
-                        9
-                    } else {
-                        // Invalid code
-                        panic!("")
-                    }
-                },
-                7 => 8,
-                _ => panic!(""),
-            })
+    // The displayed glyphs, as long as every possible wiring agrees on
+    // them. Stops at the first wiring that disagrees.
+    fn read(&self, enc: &Encoding) -> Result<Vec<usize>, String> {
+        let mut reading = None;
+        let mut other = None;
+        self.solve(enc, &mut |m| {
+            let r = self.map(enc, m);
+            match &reading {
+                None => reading = Some(r),
+                Some(first) if *first != r => other = Some(r),
+                Some(_) => (),
+            }
+            other.is_none()
+        });
+
+        let reading = reading.ok_or("No wiring is consistent")?;
+        match other {
+            Some(other) => Err(format!("possible wirings read both {} and {}",
+                                       enc.show(&reading), enc.show(&other))),
+            None => Ok(reading),
         }
+    }
+}
 
-        result.try_into().unwrap()
+fn show_mapping(map: &[usize]) -> String {
+    map.iter().enumerate().map(|(wire, &seg)| {
+        format!("{}->{}", (b'a' + wire as u8) as char, (b'a' + seg as u8) as char)
+    }).collect::<Vec<_>>().join(" ")
+}
+
+// Most wirings to list, and to count, for each line.
+const SHOW_CANDIDATES: usize = 10;
+const COUNT_CANDIDATES: usize = 1000;
+
+// Describe every line that doesn't have exactly one wiring.
+fn candidates(s: &str, enc: &Encoding) -> String {
+    let mut out = String::new();
+    for (idx, line) in s.split_terminator('\n').enumerate() {
+        let display = match Display::parse(line, enc) {
+            Ok(d) => d,
+            Err(e) => {
+                out += &format!("line {}: {}\n", idx + 1, e);
+                continue
+            },
+        };
+
+        let mut count = 0;
+        let mut shown = String::new();
+        display.solve(enc, &mut |m| {
+            count += 1;
+            if count <= SHOW_CANDIDATES {
+                shown += &format!("  {}\n", show_mapping(m));
+            }
+            count <= COUNT_CANDIDATES
+        });
+        match count {
+            0 => out += &format!("line {}: inconsistent\n", idx + 1),
+            1 => (),
+            n if n > COUNT_CANDIDATES => out += &format!(
+                "line {}: more than {} candidate wirings\n", idx + 1, COUNT_CANDIDATES),
+            n => out += &format!("line {}: {} candidate wirings\n", idx + 1, n),
+        }
+        if count > 1 {
+            out += &shown;
+        }
+        if count > SHOW_CANDIDATES {
+            out += "  ...\n";
+        }
     }
+    out
+}
+
+fn read_line(idx: usize, line: &str, enc: &Encoding) -> Vec<String> {
+    let reading = Display::parse(line, enc)
+        .and_then(|d| d.read(enc))
+        .unwrap_or_else(|e| panic!("line {}: {}", idx + 1, e));
+    reading.into_iter().map(|g| enc.glyphs[g].0.clone()).collect()
+}
+
+fn decode(s: &str, enc: &Encoding) -> String {
+    let mut out = String::new();
+    for (idx, line) in s.split_terminator('\n').enumerate() {
+        out += &read_line(idx, line, enc).join("");
+        out += "\n";
+    }
+    out
 }
 
 fn first_part(s: &str) -> usize {
+    let enc = Encoding::seven_segment();
     let mut result = 0;
-    for line in s.split_terminator('\n') {
-        let display: Display = line.parse().expect("Failed to parse line");
-        let map = display.solve();
-        result += display.map(map).iter()
-            .filter(|&&i| i == 1 || i == 4 || i == 7 || i == 8).count();
+    for (idx, line) in s.split_terminator('\n').enumerate() {
+        result += read_line(idx, line, &enc).iter()
+            .filter(|&g| g == "1" || g == "4" || g == "7" || g == "8").count();
     }
     result
 }
 
 fn second_part(s: &str) -> usize {
+    let enc = Encoding::seven_segment();
     let mut result = 0;
-    for line in s.split_terminator('\n') {
-        let display: Display = line.parse().expect("Failed to parse line");
-        let map = display.solve();
-        result += display.map(map).iter().fold(0, |x,y| (x*10)+y);
+    for (idx, line) in s.split_terminator('\n').enumerate() {
+        result += read_line(idx, line, &enc).join("").parse::<usize>()
+            .unwrap_or_else(|_| panic!("line {}: reading isn't a number", idx + 1));
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(line: &str) -> Result<String, String> {
+        let enc = Encoding::seven_segment();
+        Display::parse(line, &enc)?.read(&enc).map(|r| enc.show(&r))
+    }
+
+    #[test]
+    fn example_line() {
+        let line = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | \
+                    cdfeb fcadb cdfeb cdbaf";
+        assert_eq!(read(line), Ok("5353".to_string()));
+        assert_eq!(candidates(line, &Encoding::seven_segment()), "");
+    }
+
+    #[test]
+    fn inconsistent() {
+        // abc must be 7 and ab 1, so d lights the top segment, which 4
+        // doesn't have.
+        let line = "ab abc | abcd";
+        assert_eq!(read(line), Err("No wiring is consistent".to_string()));
+        assert_eq!(candidates(line, &Encoding::seven_segment()), "line 1: inconsistent\n");
+    }
+
+    #[test]
+    fn ambiguous_but_agreeing() {
+        let line = "ab | ab";
+        assert_eq!(read(line), Ok("1".to_string()));
+        let out = candidates(line, &Encoding::seven_segment());
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "line 1: 240 candidate wirings");
+        assert_eq!(lines.len(), SHOW_CANDIDATES + 2);
+        assert_eq!(lines.last(), Some(&"  ..."));
+    }
+
+    #[test]
+    fn ambiguous_readings() {
+        // Five segments could be a 2, 3 or 5.
+        let err = read("| abcde").unwrap_err();
+        assert!(err.starts_with("possible wirings read both"), "{}", err);
+    }
+
+    #[test]
+    fn candidates_are_capped() {
+        let out = candidates("abcdefg | abcdefg", &Encoding::seven_segment());
+        assert!(out.starts_with(&format!("line 1: more than {} candidate wirings\n",
+                                         COUNT_CANDIDATES)), "{}", out);
+        assert_eq!(out.lines().count(), SHOW_CANDIDATES + 2);
+    }
+}
@@ -14,14 +14,30 @@
  * limitations under the License.
  */
 
+use std::env;
 use std::fs;
+use std::io::{self, Write};
 use std::string::String;
-use std::collections::HashSet;
 
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
+
+    let mut args = env::args().skip(1);
+    match args.next().as_deref() {
+        Some("--basins") => {
+            print!("{}", report(&label_basins(&parse(&str))));
+            return;
+        },
+        Some("--render") => {
+            let out = render(&parse(&str), &args.collect::<Vec<_>>())
+                .unwrap_or_else(|e| panic!("{}", e));
+            io::stdout().write_all(&out).expect("Couldn't write render");
+            return;
+        },
+        _ => (),
+    }
 
     println!("{}", first_part(&str));
     println!("{}", second_part(&str));
@@ -38,96 +54,262 @@ fn parse(s: &str) -> Map {
     let mut height = 0;
     let mut map = Vec::new();
     for line in s.split_whitespace() {
+        assert!(height == 0 || line.len() == width, "Lines have different lengths");
         width = line.len();
         height += 1;
 
         for c in line.chars() {
-            map.push(((c as u32) - ('0' as u32)) as usize);
+            map.push(c.to_digit(10).expect("Height wasn't a digit") as usize);
         }
     }
 
     Map { map, width, height }
 }
 
-fn find_low_points(map: &Map) -> impl Iterator<Item=(usize, usize)> + '_ {
-    (0..map.map.len())
-        .map(move |idx| (idx % map.width, idx / map.width))
-        .filter(move |&(x,y)| {
-            let val = map.map[y*map.width + x];
-            if x != 0 && val >= map.map[y*map.width + x-1] {
-                false
-            } else if x != (map.width-1) && val >= map.map[y*map.width + x+1] {
-                false
-            } else if y != 0 && val >= map.map[(y-1)*map.width + x] {
-                false
-            } else if y != (map.height-1) && val >= map.map[(y+1)*map.width + x] {
-                false
-            } else {
-                true
-            }
-        })
+impl Map {
+    fn neighbors(&self, idx: usize) -> impl Iterator<Item=usize> {
+        let (x, y) = (idx % self.width, idx / self.width);
+        let width = self.width;
+        vec![
+            (x != 0).then(|| idx - 1),
+            (x != width - 1).then(|| idx + 1),
+            (y != 0).then(|| idx - width),
+            (y != self.height - 1).then(|| idx + width),
+        ].into_iter().flatten()
+    }
 }
 
-fn find_basin_size(map: &Map, point: (usize, usize)) -> usize {
-    let mut scanned = HashSet::new();
-    let mut frontier = HashSet::new();
-    frontier.insert(point);
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
 
-    while frontier.len() > 0 {
-        scanned = scanned.union(&frontier).cloned().collect();
-        let mut new_frontier = HashSet::new();
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        UnionFind { parent: (0..n).collect(), size: vec![1; n] }
+    }
 
-        for (x,y) in frontier {
-            let val = map.map[y*map.width + x];
+    fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
 
-            if x != 0 {
-                let new_val = map.map[y*map.width + x-1];
-                if val < new_val && new_val < 9 {
-                    new_frontier.insert((x-1, y));
-                }
-            }
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
 
-            if x != (map.width-1) {
-                let new_val = map.map[y*map.width + x+1];
-                if val < new_val && new_val < 9 {
-                    new_frontier.insert((x+1, y));
-                }
-            }
+struct Basins {
+    // Basin of each cell, None for height 9
+    labels: Vec<Option<usize>>,
+    sizes: Vec<usize>,
+    // Cells of the lowest plateau in each basin, as (x, y)
+    low_points: Vec<Vec<(usize, usize)>>,
+    // Cells next to a 9, another basin or the edge of the map
+    boundary: Vec<bool>,
+}
 
-            if y != 0 {
-                let new_val = map.map[(y-1)*map.width + x];
-                if val < new_val && new_val < 9 {
-                    new_frontier.insert((x, y-1));
-                }
+// Watershed labelling. Equal neighbouring heights are first joined
+// into plateaus, then each plateau with a lower neighbour flows into
+// the lowest one (the first found on ties). Plateaus without a lower
+// neighbour are the low points, and each basin ends up with exactly
+// one of them.
+fn label_basins(map: &Map) -> Basins {
+    let n = map.map.len();
+    let walls = |idx: usize| map.map[idx] >= 9;
+    let mut uf = UnionFind::new(n);
+
+    for idx in (0..n).filter(|&i| !walls(i)) {
+        for nb in map.neighbors(idx) {
+            if map.map[nb] == map.map[idx] {
+                uf.union(idx, nb);
             }
+        }
+    }
 
-            if y != (map.height-1) {
-                let new_val = map.map[(y+1)*map.width + x];
-                if val < new_val && new_val < 9 {
-                    new_frontier.insert((x, y+1));
-                }
+    // Lowest cell next to each plateau
+    let mut outflow: Vec<Option<usize>> = vec![None; n];
+    for idx in (0..n).filter(|&i| !walls(i)) {
+        let root = uf.find(idx);
+        for nb in map.neighbors(idx) {
+            if map.map[nb] < map.map[idx] &&
+                outflow[root].is_none_or(|o| map.map[nb] < map.map[o]) {
+                outflow[root] = Some(nb);
             }
         }
+    }
+
+    let plateau: Vec<usize> = (0..n).map(|i| uf.find(i)).collect();
+    let is_low: Vec<bool> = (0..n)
+        .map(|i| !walls(i) && outflow[plateau[i]].is_none())
+        .collect();
+    for root in (0..n).filter(|&i| !walls(i) && plateau[i] == i) {
+        if let Some(o) = outflow[root] {
+            uf.union(root, o);
+        }
+    }
+
+    let mut ids = vec![None; n];
+    let mut labels = vec![None; n];
+    let mut sizes = Vec::new();
+    let mut low_points = Vec::new();
+    for idx in (0..n).filter(|&i| !walls(i)) {
+        let root = uf.find(idx);
+        let id = *ids[root].get_or_insert_with(|| {
+            sizes.push(0);
+            low_points.push(Vec::new());
+            sizes.len() - 1
+        });
+        labels[idx] = Some(id);
+        sizes[id] += 1;
+        if is_low[idx] {
+            low_points[id].push((idx % map.width, idx / map.width));
+        }
+    }
+
+    let boundary = (0..n).map(|idx| {
+        labels[idx].is_some() && (map.neighbors(idx).count() < 4 ||
+            map.neighbors(idx).any(|nb| labels[nb] != labels[idx]))
+    }).collect();
+
+    Basins { labels, sizes, low_points, boundary }
+}
+
+fn report(basins: &Basins) -> String {
+    let boundary = basins.boundary.iter().filter(|&&b| b).count();
+    let mut out = format!("{} basins, {} cells labelled, {} on a boundary\n",
+                          basins.sizes.len(),
+                          basins.labels.iter().flatten().count(),
+                          boundary);
+    out += "basin,size,low_points\n";
+    for (id, size) in basins.sizes.iter().enumerate() {
+        let lows = basins.low_points[id].iter()
+            .map(|(x, y)| format!("{}:{}", x, y))
+            .collect::<Vec<_>>().join(" ");
+        out += &format!("{},{},{}\n", id, size, lows);
+    }
+    out
+}
+
+type Rgb = [u8; 3];
+
+// Spread the hues out by the golden angle so neighbouring ids get
+// clearly different colours.
+fn basin_colour(id: usize) -> Rgb {
+    let hue = (id as f64 * 137.508) % 360.0;
+    let (s, v) = (0.65, 0.9);
+    let c = v * s;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as usize {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    [r, g, b].map(|n| ((n + m) * 255.0) as u8)
+}
+
+fn low_cells(map: &Map, basins: &Basins) -> Vec<bool> {
+    let mut lows = vec![false; map.map.len()];
+    for &(x, y) in basins.low_points.iter().flatten() {
+        lows[y*map.width + x] = true;
+    }
+    lows
+}
+
+// Colour of each cell. Walls are black, low points white and basins
+// outside the top k grey. Basin colours get darker as the height
+// increases.
+fn colours(map: &Map, basins: &Basins, top: Option<usize>) -> Vec<Rgb> {
+    let mut ranked: Vec<usize> = (0..basins.sizes.len()).collect();
+    ranked.sort_by_key(|&id| std::cmp::Reverse(basins.sizes[id]));
+    let mut shown = vec![true; ranked.len()];
+    if let Some(k) = top {
+        for &id in ranked.iter().skip(k) {
+            shown[id] = false;
+        }
+    }
+
+    let lows = low_cells(map, basins);
+    (0..map.map.len()).map(|idx| match basins.labels[idx] {
+        None => [0, 0, 0],
+        Some(_) if lows[idx] => [255, 255, 255],
+        Some(id) if !shown[id] => [96, 96, 96],
+        Some(id) => {
+            let shade = 10 - map.map[idx];
+            basin_colour(id).map(|c| (c as usize * shade / 10) as u8)
+        },
+    }).collect()
+}
 
-        frontier = new_frontier.difference(&scanned).cloned().collect();
+fn to_ppm(map: &Map, colours: &[Rgb]) -> Vec<u8> {
+    let mut out = format!("P6\n{} {}\n255\n", map.width, map.height).into_bytes();
+    out.extend(colours.iter().flatten());
+    out
+}
+
+// Each cell is its height on a background of its colour, with low
+// points marked by '*'.
+fn to_ansi(map: &Map, basins: &Basins, colours: &[Rgb]) -> String {
+    let lows = low_cells(map, basins);
+    let mut out = String::new();
+    for row in 0..map.height {
+        let cells = row*map.width..(row+1)*map.width;
+        for ((&h, &[r, g, b]), &low) in map.map[cells.clone()].iter()
+            .zip(&colours[cells.clone()]).zip(&lows[cells]) {
+            let text = if low { "*".to_string() } else { h.to_string() };
+            let fg = if (r as usize + g as usize + b as usize) > 384 { 30 } else { 97 };
+            out += &format!("\x1b[{};48;2;{};{};{}m{}", fg, r, g, b, text);
+        }
+        out += "\x1b[0m\n";
     }
+    out
+}
+
+// Arguments are the format (ppm or ansi) optionally followed by
+// --top k to only colour the k largest basins.
+fn render(map: &Map, args: &[String]) -> Result<Vec<u8>, String> {
+    let format = args.first().ok_or("Usage: day09 --render <ppm|ansi> [--top k]")?;
+    let top = match &args[1..] {
+        [] => None,
+        [flag, k] if flag == "--top" =>
+            Some(k.parse().or(Err(format!("Invalid basin count {}", k)))?),
+        _ => return Err(format!("Unknown arguments {:?}", &args[1..])),
+    };
 
-    scanned.len()
+    let basins = label_basins(map);
+    let colours = colours(map, &basins, top);
+    match format.as_str() {
+        "ppm" => Ok(to_ppm(map, &colours)),
+        "ansi" => Ok(to_ansi(map, &basins, &colours).into_bytes()),
+        _ => Err(format!("Unknown render format {}", format)),
+    }
 }
 
 fn first_part(s: &str) -> usize {
     let map = parse(s);
-    find_low_points(&map).fold(0, |sum,(x,y)| {
-        sum + map.map[y*map.width + x] + 1
-    })
+    label_basins(&map).low_points.iter().flatten()
+        .map(|&(x, y)| map.map[y*map.width + x] + 1)
+        .sum()
 }
 
 fn second_part(s: &str) -> usize {
     let map = parse(s);
-    let mut basins = find_low_points(&map)
-        .map(|x| find_basin_size(&map, x))
-        .collect::<Vec<_>>();
+    let mut basins = label_basins(&map).sizes;
     basins.sort_unstable_by(|x,y| y.cmp(x));
-    basins.iter().take(3)
-        .fold(1, |x,y| x*y)
+    basins.iter().take(3).product()
 }
@@ -14,16 +14,233 @@
  * limitations under the License.
  */
 
+use std::env;
+use std::fmt;
 use std::fs;
 use std::string::String;
+use std::str::FromStr;
 
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
 
-    println!("{}", first_part(&str));
-    println!("{}", second_part(&str));
+    let mut grammar = Grammar::default();
+    let mut errors = None;
+    let mut repair_to = None;
+    let mut args = env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--grammar" => {
+                let file = args.next().expect("Usage: day10 --grammar <file>");
+                grammar = fs::read_to_string(file).expect("Couldn't read grammar")
+                    .parse().unwrap_or_else(|e| panic!("{}", e));
+            },
+            "--errors" => {
+                // The mode is optional, so leave any following flag alone
+                let mode = args.next_if(|a| !a.starts_with("--"));
+                errors = Some(mode.as_deref().unwrap_or("insert").parse()
+                              .expect("Usage: day10 --errors [stop|skip|insert]"));
+            },
+            "--repair" => {
+                repair_to = Some(args.next().expect("Usage: day10 --repair <output file>"));
+            },
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
+
+    if let Some(out) = repair_to {
+        let (fixed, report) = repair(&str, &grammar);
+        fs::write(out, fixed).expect("Couldn't write repaired file");
+        print!("{}", report);
+        return;
+    }
+
+    if let Some(recovery) = errors {
+        print!("{}", error_report(&str, &grammar, recovery));
+        return;
+    }
+
+    println!("{}", first_part(&str, &grammar));
+    println!("{}", second_part(&str, &grammar));
+}
+
+#[derive(Clone, Debug)]
+struct Pair {
+    open: char,
+    close: char,
+    // Score for a corrupt line ending on this closer, and for each
+    // use of it when completing a line
+    corrupt: usize,
+    complete: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Grammar {
+    pairs: Vec<Pair>,
+}
+
+impl Default for Grammar {
+    fn default() -> Self {
+        let pairs = [('(', ')', 3, 1), ('[', ']', 57, 2),
+                     ('{', '}', 1197, 3), ('<', '>', 25137, 4)];
+        Grammar {
+            pairs: pairs.iter().map(|&(open, close, corrupt, complete)| {
+                Pair { open, close, corrupt, complete }
+            }).collect(),
+        }
+    }
+}
+
+// One pair per line, written as `<open> <close> <corrupt score>
+// <completion score>`. Blank lines and lines starting with '#' are
+// ignored.
+impl FromStr for Grammar {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pairs: Vec<Pair> = Vec::new();
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let err = || format!("line {}: expected <open> <close> <corrupt> <complete>", idx + 1);
+            let fields: Vec<_> = line.split_whitespace().collect();
+            let pair = match fields[..] {
+                [open, close, corrupt, complete] if open.chars().count() == 1 &&
+                    close.chars().count() == 1 => Pair {
+                        open: open.chars().next().unwrap(),
+                        close: close.chars().next().unwrap(),
+                        corrupt: corrupt.parse().map_err(|_| err())?,
+                        complete: complete.parse().map_err(|_| err())?,
+                    },
+                _ => return Err(err()),
+            };
+            if pair.open == pair.close || pairs.iter().any(|p| {
+                [p.open, p.close].iter().any(|c| [pair.open, pair.close].contains(c))
+            }) {
+                return Err(format!("line {}: bracket used twice", idx + 1));
+            }
+            pairs.push(pair);
+        }
+        if pairs.is_empty() {
+            return Err("Grammar has no pairs".to_string());
+        }
+        Ok(Grammar { pairs })
+    }
+}
+
+impl Grammar {
+    fn opener(&self, c: char) -> Option<&Pair> {
+        self.pairs.iter().find(|p| p.open == c)
+    }
+
+    fn closer(&self, c: char) -> Option<&Pair> {
+        self.pairs.iter().find(|p| p.close == c)
+    }
+
+    // Completion scores are read as digits in this base, so each
+    // completion string gets its own score. This is 5 for the puzzle.
+    fn completion_base(&self) -> usize {
+        self.pairs.iter().map(|p| p.complete).max().unwrap_or(0) + 1
+    }
+}
+
+// What to do on finding an error. Stop gives up on the rest of the
+// line, Skip drops the bad character, and Insert closes any open
+// chunks between the top of the stack and the one the character
+// closes, only skipping if nothing open matches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Recovery {
+    Stop,
+    Skip,
+    Insert,
+}
+
+impl FromStr for Recovery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stop" => Ok(Recovery::Stop),
+            "skip" => Ok(Recovery::Skip),
+            "insert" => Ok(Recovery::Insert),
+            _ => Err(format!("Unknown recovery mode {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Fix {
+    Stopped,
+    Skipped,
+    Inserted(String),
+}
+
+#[derive(Clone, Debug)]
+struct ParseError {
+    // Column of the bad character, counting from 1
+    column: usize,
+    found: char,
+    // Closer for the innermost open chunk, if any
+    expected: Option<char>,
+    fix: Fix,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.expected {
+            Some(e) => write!(f, "column {}: expected {:?}, found {:?}",
+                              self.column, e, self.found)?,
+            None => write!(f, "column {}: unexpected {:?}", self.column, self.found)?,
+        }
+        match &self.fix {
+            Fix::Stopped => Ok(()),
+            Fix::Skipped => write!(f, " (skipped)"),
+            Fix::Inserted(s) => write!(f, " (inserted {:?})", s),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Checked {
+    errors: Vec<ParseError>,
+    // Closers needed to finish the line, innermost first
+    completion: String,
+}
+
+fn check(line: &str, grammar: &Grammar, recovery: Recovery) -> Checked {
+    let mut stack: Vec<char> = Vec::new();
+    let mut errors = Vec::new();
+    for (idx, c) in line.chars().enumerate() {
+        if let Some(p) = grammar.opener(c) {
+            stack.push(p.close);
+            continue
+        }
+        if grammar.closer(c).is_some() && stack.last() == Some(&c) {
+            stack.pop();
+            continue
+        }
+
+        let expected = stack.last().cloned();
+        let fix = match recovery {
+            Recovery::Stop => Fix::Stopped,
+            Recovery::Insert if grammar.closer(c).is_some() && stack.contains(&c) => {
+                let depth = stack.iter().rposition(|&d| d == c).unwrap();
+                let inserted = stack.drain(depth..).skip(1).rev().collect();
+                Fix::Inserted(inserted)
+            },
+            _ => Fix::Skipped,
+        };
+        let stop = fix == Fix::Stopped;
+        errors.push(ParseError { column: idx + 1, found: c, expected, fix });
+        if stop {
+            break
+        }
+    }
+
+    Checked { errors, completion: stack.iter().rev().collect() }
 }
 
 enum ParseResult {
@@ -32,68 +249,296 @@ enum ParseResult {
     Complete,
 }
 
-fn parse(line: &str) -> ParseResult {
-    let mut stack = Vec::new();
-    for c in line.chars() {
-        match c {
-            '(' => stack.push(')'),
-            '[' => stack.push(']'),
-            '{' => stack.push('}'),
-            '<' => stack.push('>'),
-            _ => match stack.pop() {
-                Some(d) => if c != d {
-                    return ParseResult::Corrupt(c)
-                },
-                None => return ParseResult::Corrupt(c)
-            },
+fn parse(line: &str, grammar: &Grammar) -> ParseResult {
+    let checked = check(line, grammar, Recovery::Stop);
+    match checked.errors.first() {
+        Some(e) => ParseResult::Corrupt(e.found),
+        None if checked.completion.is_empty() => ParseResult::Complete,
+        None => ParseResult::Incomplete(checked.completion),
+    }
+}
+
+fn error_report(s: &str, grammar: &Grammar, recovery: Recovery) -> String {
+    let mut out = String::new();
+    for (idx, line) in s.lines().enumerate() {
+        let checked = check(line, grammar, recovery);
+        for e in &checked.errors {
+            out += &format!("line {}, {}\n", idx + 1, e);
+        }
+        if !checked.completion.is_empty() && checked.errors.last()
+            .is_none_or(|e| e.fix != Fix::Stopped) {
+            out += &format!("line {}: incomplete, missing {:?}\n", idx + 1, checked.completion);
         }
     }
+    out
+}
+
+#[derive(Clone, Debug)]
+enum Edit {
+    Replace { column: usize, from: char, to: char, partner: usize },
+    Delete { column: usize, c: char },
+}
 
-    if stack.len() == 0 {
-        ParseResult::Complete
-    } else {
-        ParseResult::Incomplete(stack.iter().rev().collect())
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Edit::Replace { column, from, to, partner } =>
+                write!(f, "column {}: replaced {:?} with {:?} to pair with column {}",
+                       column, from, to, partner),
+            Edit::Delete { column, c } =>
+                write!(f, "column {}: deleted {:?}, nothing to pair it with", column, c),
+        }
     }
 }
 
-fn first_part(s: &str) -> usize {
-    let mut result = 0;
-    for l in s.split_whitespace() {
-        match parse(l) {
-            ParseResult::Corrupt(c) => match c {
-                ')' => result += 3,
-                ']' => result += 57,
-                '}' => result += 1197,
-                '>' => result += 25137,
-                _ => unreachable!(),
-            },
-            _ => {},
+// Fewest edits that turn a line into a valid, possibly incomplete,
+// line. Inserting a bracket never does better than deleting the one
+// it would pair with, so only replacements and deletions are used.
+struct Repairer<'a> {
+    chars: Vec<char>,
+    grammar: &'a Grammar,
+    // Edits to make chars[i..j] balanced, indexed by i*(n+1) + j
+    balanced: Vec<usize>,
+    // Edits to make chars[i..] a valid prefix
+    prefix: Vec<usize>,
+}
+
+impl<'a> Repairer<'a> {
+    fn new(line: &str, grammar: &'a Grammar) -> Self {
+        let chars: Vec<char> = line.chars().collect();
+        let n = chars.len();
+        let mut r = Repairer {
+            chars, grammar, balanced: vec![0; (n+1)*(n+1)], prefix: vec![0; n+1],
+        };
+
+        for len in 1..=n {
+            for i in 0..=n-len {
+                let j = i + len;
+                let cost = (i+1..j).map(|k| r.pair_choice(i, k, j).0)
+                    .fold(r.bal(i+1, j) + 1, usize::min);
+                r.balanced[i*(n+1) + j] = cost;
+            }
         }
+        for i in (0..n).rev() {
+            r.prefix[i] = (i..n).filter(|&k| grammar.opener(r.chars[k]).is_some())
+                .map(|k| r.bal(i, k) + r.prefix[k+1])
+                .fold(r.bal(i, n), usize::min);
+        }
+        r
+    }
+
+    fn bal(&self, i: usize, j: usize) -> usize {
+        self.balanced[i*(self.chars.len()+1) + j]
+    }
+
+    // Cost of pairing chars[i] with chars[k] inside chars[i..j], and
+    // the pair to use.
+    fn pair_choice(&self, i: usize, k: usize, j: usize) -> (usize, &'a Pair) {
+        let sub = |c: char, d: char| (c != d) as usize;
+        let inner = self.bal(i+1, k) + self.bal(k+1, j);
+        self.grammar.pairs.iter()
+            .map(|p| (inner + sub(self.chars[i], p.open) + sub(self.chars[k], p.close), p))
+            .min_by_key(|&(cost, _)| cost).unwrap()
+    }
+
+    fn edits_balanced(&self, i: usize, j: usize, edits: &mut Vec<Edit>) {
+        if i >= j {
+            return
+        }
+        let target = self.bal(i, j);
+        for k in i+1..j {
+            let (cost, p) = self.pair_choice(i, k, j);
+            if cost == target {
+                for (idx, to, partner) in [(i, p.open, k), (k, p.close, i)] {
+                    if self.chars[idx] != to {
+                        edits.push(Edit::Replace {
+                            column: idx + 1, from: self.chars[idx], to, partner: partner + 1,
+                        });
+                    }
+                }
+                self.edits_balanced(i+1, k, edits);
+                self.edits_balanced(k+1, j, edits);
+                return
+            }
+        }
+        edits.push(Edit::Delete { column: i + 1, c: self.chars[i] });
+        self.edits_balanced(i+1, j, edits);
+    }
+
+    fn edits(&self) -> Vec<Edit> {
+        let n = self.chars.len();
+        let mut edits = Vec::new();
+        let mut i = 0;
+        while i < n {
+            if self.prefix[i] == self.bal(i, n) {
+                self.edits_balanced(i, n, &mut edits);
+                break
+            }
+            // Otherwise some opener is left open after a balanced run
+            let k = (i..n).find(|&k| self.grammar.opener(self.chars[k]).is_some() &&
+                                self.bal(i, k) + self.prefix[k+1] == self.prefix[i]).unwrap();
+            self.edits_balanced(i, k, &mut edits);
+            i = k + 1;
+        }
+        edits.sort_by_key(|e| match e {
+            Edit::Replace { column, .. } | Edit::Delete { column, .. } => *column,
+        });
+        edits
     }
-    result
 }
 
-fn second_part(s: &str) -> usize {
+fn apply(line: &str, edits: &[Edit]) -> String {
+    let mut chars: Vec<Option<char>> = line.chars().map(Some).collect();
+    for e in edits {
+        match *e {
+            Edit::Replace { column, to, .. } => chars[column - 1] = Some(to),
+            Edit::Delete { column, .. } => chars[column - 1] = None,
+        }
+    }
+    chars.into_iter().flatten().collect()
+}
+
+// Returns the repaired file, and a report of what changed on each
+// line that needed fixing.
+fn repair(s: &str, grammar: &Grammar) -> (String, String) {
+    let mut fixed = String::new();
+    let mut report = String::new();
+    let mut changed = 0;
+    for (idx, line) in s.lines().enumerate() {
+        let edits = match parse(line, grammar) {
+            ParseResult::Corrupt(_) => Repairer::new(line, grammar).edits(),
+            _ => Vec::new(),
+        };
+        let mut new = apply(line, &edits);
+        let completion = check(&new, grammar, Recovery::Stop).completion;
+        new += &completion;
+
+        if new != line {
+            changed += 1;
+            report += &format!("line {}: {} edit{}{}\n", idx + 1, edits.len(),
+                               if edits.len() == 1 { "" } else { "s" },
+                               if completion.is_empty() { "" } else { ", completed" });
+            report += &format!("- {}\n+ {}\n", line, new);
+            for e in &edits {
+                report += &format!("  {}\n", e);
+            }
+            if !completion.is_empty() {
+                report += &format!("  appended {:?} to close open chunks\n", completion);
+            }
+        }
+        fixed += &new;
+        fixed += "\n";
+    }
+    report += &format!("{} of {} lines changed\n", changed, s.lines().count());
+    (fixed, report)
+}
+
+fn first_part(s: &str, grammar: &Grammar) -> usize {
+    s.split_whitespace().map(|l| match parse(l, grammar) {
+        ParseResult::Corrupt(c) => grammar.closer(c).map_or(0, |p| p.corrupt),
+        _ => 0,
+    }).sum()
+}
+
+fn second_part(s: &str, grammar: &Grammar) -> usize {
+    let base = grammar.completion_base();
     let mut scores = Vec::new();
     for l in s.split_whitespace() {
-        let mut score = 0;
-        match parse(l) {
-            ParseResult::Incomplete(stack) => {
-                for c in stack.chars() {
-                    score *= 5;
-                    match c {
-                        ')' => score += 1,
-                        ']' => score += 2,
-                        '}' => score += 3,
-                        '>' => score += 4,
-                        _ => unreachable!(),
-                    }
-                }
-                scores.push(score);
-            },
-            _ => {},
+        if let ParseResult::Incomplete(stack) = parse(l, grammar) {
+            scores.push(stack.chars().fold(0, |score, c| {
+                score * base + grammar.closer(c).unwrap().complete
+            }));
         }
     }
     scores.sort_unstable();
     scores[scores.len()/2]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+[({(<(())[]>[[{[]{<()<>>
+[(()[<>])]({[<{<<[]>>(
+{([(<{}[<>[]}>{[]{[(<()>
+(((({<>}<{<{<>}{[]{[]{}
+[[<[([]))<([[{}[[()]]]
+[{[{({}]{}}([{[{{{}}([]
+{<[[]]>}<{[{[{[]{()[[[]
+[<(<(<(<{}))><([]([]()
+<{([([[(<>()){}]>(<<{{
+<{([{{}}[<[[[<>{}]]]>[]]
+";
+
+    fn repaired(line: &str) -> (usize, String) {
+        let grammar = Grammar::default();
+        let edits = Repairer::new(line, &grammar).edits();
+        (edits.len(), apply(line, &edits))
+    }
+
+    #[test]
+    fn example_first_part() {
+        assert_eq!(first_part(EXAMPLE, &Grammar::default()), 26397);
+    }
+
+    #[test]
+    fn example_second_part() {
+        assert_eq!(second_part(EXAMPLE, &Grammar::default()), 288957);
+    }
+
+    #[test]
+    fn example_repairs() {
+        let expected = [
+            (0, "[({(<(())[]>[[{[]{<()<>>"),
+            (0, "[(()[<>])]({[<{<<[]>>("),
+            (1, "{([(<{}[<>[]<>{[]{[(<()>"),
+            (0, "(((({<>}<{<{<>}{[]{[]{}"),
+            (1, "[[<[([]()<([[{}[[()]]]"),
+            (1, "[{[{({}{{}}([{[{{{}}([]"),
+            (0, "{<[[]]>}<{[{[{[]{()[[[]"),
+            (1, "[<(<(<(<{}()><([]([]()"),
+            (1, "<{([([[(<>()){}<>(<<{{"),
+            (0, "<{([{{}}[<[[[<>{}]]]>[]]"),
+        ];
+        for (line, (count, fixed)) in EXAMPLE.lines().zip(expected) {
+            assert_eq!(repaired(line), (count, fixed.to_string()), "{}", line);
+        }
+    }
+
+    #[test]
+    fn example_repair_completes_every_line() {
+        let (fixed, _) = repair(EXAMPLE, &Grammar::default());
+        for line in fixed.lines() {
+            assert!(matches!(parse(line, &Grammar::default()), ParseResult::Complete),
+                    "{}", line);
+        }
+    }
+
+    #[test]
+    fn leading_closer() {
+        assert_eq!(repaired(")"), (1, "".to_string()));
+    }
+
+    #[test]
+    fn non_bracket_characters() {
+        assert_eq!(repaired("ab(]"), (3, "()()".to_string()));
+        assert_eq!(repaired("a(]]"), (2, "[()]".to_string()));
+    }
+
+    #[test]
+    fn delete_and_replace() {
+        let grammar = Grammar::default();
+        let edits = Repairer::new(")(]", &grammar).edits();
+        assert!(matches!(edits[..], [
+            Edit::Replace { column: 1, from: ')', to: '[', partner: 3 },
+            Edit::Delete { column: 2, c: '(' },
+        ]));
+        assert_eq!(apply(")(]", &edits), "[]");
+    }
+
+    #[test]
+    fn misplaced_closer() {
+        assert_eq!(repaired("([)]"), (1, "([]".to_string()));
+    }
+}
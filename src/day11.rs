@@ -17,20 +17,64 @@
 use std::fs;
 use std::string::String;
 use std::str::FromStr;
-use std::convert::TryInto;
+use std::env;
+use std::collections::{HashMap, VecDeque};
 use itertools::Itertools;
 
+mod repl;
+
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
+
+    let mut interactive = false;
+    let mut period = false;
+    let mut wrap = false;
+    let mut threshold = 9;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--interactive" => interactive = true,
+            "--period" => period = true,
+            "--wrap" => wrap = true,
+            "--threshold" => threshold = args.next().and_then(|n| n.parse().ok())
+                .expect("Usage: day11 --threshold <energy>"),
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
+    let mut grid: Grid = str.parse().expect("Failed to parse problem");
+    grid.wrap = wrap;
+    grid.threshold = threshold;
+
+    if interactive {
+        repl::run(Session { grid, flashes: 0 });
+        return;
+    }
+
+    if period {
+        let cycle = find_cycle(&grid);
+        println!("Periodic from step {} with period {}", cycle.start, cycle.period);
+        match cycle.first_sync {
+            Some(i) => println!("First synchronised on step {}", i),
+            None => println!("Never synchronises"),
+        }
+        return;
+    }
 
-    println!("{}", first_part(&str));
-    println!("{}", second_part(&str));
+    println!("{}", first_part(&grid));
+    println!("{}", second_part(&grid));
 }
 
+// An octopus flashes once its energy goes above the threshold. With
+// wrap set the edges of the grid join up, making it a torus.
+#[derive(Clone)]
 struct Grid {
-    map: [[u32; 10]; 10],
+    map: Vec<u32>,
+    width: usize,
+    height: usize,
+    wrap: bool,
+    threshold: u32,
 }
 
 impl FromStr for Grid {
@@ -38,64 +82,81 @@ impl FromStr for Grid {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut map = Vec::new();
+        let mut width = 0;
+        let mut height = 0;
         for line in s.split_whitespace() {
-            let mut l = Vec::new();
+            if height != 0 && line.len() != width {
+                return Err("Lines have different lengths");
+            }
+            width = line.len();
+            height += 1;
             for c in line.chars() {
-                l.push(c as u32 - '0' as u32);
+                map.push(c.to_digit(10).ok_or("Energy wasn't a digit")?);
             }
-            map.push(l.try_into().or(
-                Err("Line wasn't 10 characters long!"))?);
         }
-        Ok(Grid { map: map.try_into().or(
-        Err("Map wasn't 10 lines long!"))? })
+        if map.is_empty() {
+            return Err("Map is empty");
+        }
+        Ok(Grid { map, width, height, wrap: false, threshold: 9 })
     }
 }
 
-fn get_neighbors(x: usize, y: usize) -> impl Iterator<Item=(usize,usize)> {
-    (-1..2isize).cartesian_product(-1..2isize)
-        .filter(move |&(dx,dy)| {
-            !(x == 0 && dx < 0 ||
-              x == 9 && dx > 0 ||
-              y == 0 && dy < 0 ||
-              y == 9 && dy > 0 ||
-              dx == 0 && dy == 0)
-        })
-        .map(move |(dx,dy)| ((x as isize + dx) as usize,
-                             (y as isize + dy) as usize))
-}
-
 impl Grid {
-    fn step(&mut self) -> [[bool; 10]; 10] {
-        let mut result = [[false; 10]; 10];
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn get_neighbors(&self, idx: usize) -> Vec<usize> {
+        let (x, y) = ((idx % self.width) as isize, (idx / self.width) as isize);
+        let (w, h) = (self.width as isize, self.height as isize);
+        let mut result: Vec<usize> = (-1..2isize).cartesian_product(-1..2isize)
+            .filter(|&d| d != (0, 0))
+            .filter_map(|(dx, dy)| {
+                let (nx, ny) = (x + dx, y + dy);
+                if self.wrap {
+                    Some((nx.rem_euclid(w), ny.rem_euclid(h)))
+                } else if (0..w).contains(&nx) && (0..h).contains(&ny) {
+                    Some((nx, ny))
+                } else {
+                    None
+                }
+            })
+            .map(|(nx, ny)| (ny * w + nx) as usize)
+            .filter(|&n| n != idx)
+            .collect();
+        // Small wrapped grids can reach the same cell more than one way
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
 
-        for x in 0..10 {
-            for y in 0..10 {
-                self.map[x][y] += 1
+    // Octopuses are queued as they go over the threshold, so each one
+    // that flashes only bumps its neighbours once.
+    fn step(&mut self) -> Vec<bool> {
+        let mut result = vec![false; self.len()];
+        let mut queue = VecDeque::new();
+
+        for (idx, val) in self.map.iter_mut().enumerate() {
+            *val += 1;
+            if *val > self.threshold {
+                result[idx] = true;
+                queue.push_back(idx);
             }
         }
 
-        let mut done = false;
-        while !done {
-            done = true;
-
-            for x in 0..10 {
-                for y in 0..10 {
-                    if self.map[x][y] > 9 && !result[x][y] {
-                        done = false;
-                        result[x][y] = true;
-                        for (nx,ny) in get_neighbors(x,y) {
-                            self.map[nx][ny] += 1;
-                        }
-                    }
+        while let Some(idx) = queue.pop_front() {
+            for n in self.get_neighbors(idx) {
+                self.map[n] += 1;
+                if self.map[n] > self.threshold && !result[n] {
+                    result[n] = true;
+                    queue.push_back(n);
                 }
             }
         }
 
-        for x in 0..10 {
-            for y in 0..10 {
-                if result[x][y]{
-                    self.map[x][y] = 0;
-                }
+        for (val, &flashed) in self.map.iter_mut().zip(result.iter()) {
+            if flashed {
+                *val = 0;
             }
         }
 
@@ -103,29 +164,68 @@ impl Grid {
     }
 }
 
-fn first_part(s: &str) -> usize {
-    let mut grid: Grid = s.parse().expect("Failed to parse problem");
-    let mut result = 0;
-    for _ in 0..100 {
-        let flashes = grid.step();
-        for i in flashes.iter().flatten().cloned() {
-            if i {
-                result += 1;
-            }
+struct Cycle {
+    // The state after step start is the first to repeat, period steps
+    // later
+    start: usize,
+    period: usize,
+    // First step where every octopus flashed
+    first_sync: Option<usize>,
+}
+
+// After each step every energy is at most the threshold, so there are
+// finitely many states and the grid always ends up in a cycle.
+fn find_cycle(grid: &Grid) -> Cycle {
+    let mut grid = grid.clone();
+    let mut seen = HashMap::new();
+    let mut first_sync = None;
+    seen.insert(grid.map.clone(), 0);
+
+    for i in 1.. {
+        if grid.step().iter().all(|&x| x) && first_sync.is_none() {
+            first_sync = Some(i);
+        }
+        if let Some(start) = seen.insert(grid.map.clone(), i) {
+            return Cycle { start, period: i - start, first_sync };
         }
     }
-    result
+    unreachable!()
+}
+
+#[derive(Clone)]
+struct Session {
+    grid: Grid,
+    flashes: usize,
 }
 
-fn second_part(s: &str) -> usize {
-    let mut grid: Grid = s.parse().expect("Failed to parse problem");
+impl repl::Sim for Session {
+    fn step(&mut self) -> Result<(), String> {
+        self.flashes = self.grid.step().iter().filter(|&&x| x).count();
+        Ok(())
+    }
+
+    fn show(&self) -> String {
+        let mut out = format!("Flashes last step: {}\n", self.flashes);
+        for row in self.grid.map.chunks(self.grid.width) {
+            out += &row.iter().map(|v| v.to_string()).collect::<String>();
+            out += "\n";
+        }
+        out
+    }
 
-    let mut i = 0;
-    loop {
-        i += 1;
-        let flashes = grid.step().iter().flatten().filter(|&&x| x).count();
-        if flashes == 100 {
-            return i
+    fn check(&self, cond: &str) -> Result<bool, String> {
+        match cond {
+            "sync" => Ok(self.flashes == self.grid.len()),
+            _ => Err(format!("Unknown condition {}, expected sync", cond)),
         }
     }
 }
+
+fn first_part(grid: &Grid) -> usize {
+    let mut grid = grid.clone();
+    (0..100).map(|_| grid.step().iter().filter(|&&x| x).count()).sum()
+}
+
+fn second_part(grid: &Grid) -> usize {
+    find_cycle(grid).first_sync.expect("Grid never synchronises")
+}
@@ -18,16 +18,25 @@ use std::fs;
 use std::string::String;
 use std::collections::HashMap;
 use std::hash::Hash;
+use std::env;
+
+mod repl;
 
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
+
+    if env::args().nth(1).as_deref() == Some("--interactive") {
+        repl::run(parse(&str));
+        return;
+    }
 
     println!("{}", first_part(&str));
     println!("{}", second_part(&str));
 }
 
+#[derive(Clone)]
 struct Problem {
     pairs: HashMap<(char, char), usize>,
     rules: HashMap<(char, char), ((char, char), (char, char))>,
@@ -126,6 +135,37 @@ fn run_steps(mut problem: Problem, steps: usize) -> usize {
     max - min
 }
 
+impl repl::Sim for Problem {
+    fn step(&mut self) -> Result<(), String> {
+        *self = step(self.clone());
+        Ok(())
+    }
+
+    fn show(&self) -> String {
+        let length: usize = self.pairs.values().sum::<usize>() + 1;
+        let mut counts = count(self.clone()).into_iter().collect::<Vec<_>>();
+        counts.sort_unstable();
+
+        let mut out = format!("Polymer length: {}\n", length);
+        for (c, n) in counts {
+            out += &format!("{}: {}\n", c, n);
+        }
+        out
+    }
+
+    fn check(&self, cond: &str) -> Result<bool, String> {
+        let length: usize = self.pairs.values().sum::<usize>() + 1;
+        match cond.split_once(' ') {
+            Some(("length", n)) => {
+                let n: usize = n.trim().parse()
+                    .or(Err(format!("Not a length: {}", n)))?;
+                Ok(length >= n)
+            },
+            _ => Err(format!("Unknown condition {}, expected length <n>", cond)),
+        }
+    }
+}
+
 fn first_part(s: &str) -> usize {
     let problem = parse(s);
     run_steps(problem, 10)
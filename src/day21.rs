@@ -19,11 +19,19 @@ use std::string::String;
 use itertools::Itertools;
 use std::collections::HashMap;
 use std::cmp::max;
+use std::env;
+
+mod repl;
 
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
+
+    if env::args().nth(1).as_deref() == Some("--interactive") {
+        repl::run(Game::new(parse(&str), (1..101).cycle()));
+        return;
+    }
 
     println!("{}", first_part(&str));
     println!("{}", second_part(&str));
@@ -53,36 +61,89 @@ fn get_roll<I>(it: &mut I) -> u8
         it.next().unwrap() % 10
 }
 
-fn run_game<I>(start: (u8,u8), mut it: I) -> GameResult
+#[derive(Clone)]
+struct Game<I> {
+    it: I,
+    pos: (u8,u8),
+    score: (usize, usize),
+    turns: usize,
+    p1_turn: bool,
+}
+
+impl<I> Game<I>
     where I : Iterator<Item=u8> {
+    fn new(start: (u8,u8), it: I) -> Self {
+        Game { it, pos: start, score: (0,0), turns: 0, p1_turn: true }
+    }
 
-    let mut score = (0,0);
-    let mut pos = start;
-    let mut turns = 0;
+    fn is_over(&self) -> bool {
+        self.score.0 >= 1000 || self.score.1 >= 1000
+    }
 
-    let p1_wins = loop {
-        let p1_roll = get_roll(&mut it);
-        pos.0 += p1_roll;
-        pos.0 %= 10;
-        score.0 += pos.0 as usize + 1;
-        turns += 1;
-
-        if score.0 >= 1000 {
-            break true;
+    // Run a single turn, returning whether player 1 won if this turn
+    // ended the game.
+    fn run_step(&mut self) -> Option<bool> {
+        let p1_turn = self.p1_turn;
+        let roll = get_roll(&mut self.it);
+        let (pos, score) = if p1_turn {
+            (&mut self.pos.0, &mut self.score.0)
+        } else {
+            (&mut self.pos.1, &mut self.score.1)
+        };
+
+        *pos += roll;
+        *pos %= 10;
+        *score += *pos as usize + 1;
+        self.turns += 1;
+        self.p1_turn = !p1_turn;
+
+        if *score >= 1000 {
+            Some(p1_turn)
+        } else {
+            None
         }
+    }
+}
+
+impl<I> repl::Sim for Game<I>
+    where I : Iterator<Item=u8> + Clone {
+    fn step(&mut self) -> Result<(), String> {
+        if self.is_over() {
+            return Err("Game is over".to_string());
+        }
+        self.run_step();
+        Ok(())
+    }
+
+    fn show(&self) -> String {
+        format!("Turns: {}, rolls: {}\n\
+                 Player 1: position {}, score {}\n\
+                 Player 2: position {}, score {}\n",
+                self.turns, self.turns * 3,
+                self.pos.0 + 1, self.score.0,
+                self.pos.1 + 1, self.score.1)
+    }
 
-        let p2_roll = get_roll(&mut it);
-        pos.1 += p2_roll;
-        pos.1 %= 10;
-        score.1 += pos.1 as usize + 1;
-        turns += 1;
+    fn check(&self, cond: &str) -> Result<bool, String> {
+        match cond {
+            "win" => Ok(self.is_over()),
+            _ => Err(format!("Unknown condition {}, expected win", cond)),
+        }
+    }
+}
 
-        if score.1 >= 1000 {
-            break false;
+fn run_game<I>(start: (u8,u8), it: I) -> GameResult
+    where I : Iterator<Item=u8> {
+
+    let mut game = Game::new(start, it);
+
+    let p1_wins = loop {
+        if let Some(p1_wins) = game.run_step() {
+            break p1_wins;
         }
     };
 
-    GameResult { turns, p1_wins, score }
+    GameResult { turns: game.turns, p1_wins, score: game.score }
 }
 
 fn first_part(s: &str) -> usize {
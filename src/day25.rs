@@ -16,22 +16,30 @@
 
 use std::fs;
 use std::string::String;
+use std::env;
+
+mod repl;
 
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
+
+    if env::args().nth(1).as_deref() == Some("--interactive") {
+        repl::run(parse(&str));
+        return;
+    }
 
     println!("{}", first_part(&str));
 }
 
-#[derive(PartialEq,Eq)]
+#[derive(PartialEq,Eq,Clone)]
 enum Move {
     Down,
     Right,
 }
 
-#[derive(PartialEq,Eq)]
+#[derive(PartialEq,Eq,Clone)]
 struct Map {
     width: usize,
     height: usize,
@@ -124,6 +132,35 @@ fn run_step(m: &Map) -> Map {
     run_down_step(&run_right_step(m))
 }
 
+impl repl::Sim for Map {
+    fn step(&mut self) -> Result<(), String> {
+        *self = run_step(self);
+        Ok(())
+    }
+
+    fn show(&self) -> String {
+        let mut out = String::new();
+        for line in self.map.iter() {
+            for point in line.iter() {
+                out.push(match point {
+                    Some(Move::Right) => '>',
+                    Some(Move::Down) => 'v',
+                    None => '.',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn check(&self, cond: &str) -> Result<bool, String> {
+        match cond {
+            "stopped" => Ok(run_step(self) == *self),
+            _ => Err(format!("Unknown condition {}, expected stopped", cond)),
+        }
+    }
+}
+
 fn first_part(s: &str) -> usize {
     let mut map = parse(s);
     let mut count = 0;
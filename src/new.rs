@@ -0,0 +1,91 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Scaffold a new day from src/template.rs. Run from the repository
+// root as `cargo run --bin new -- <day>`.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::string::String;
+
+fn main() {
+    let arg = env::args().nth(1).expect("Usage: new <day>");
+    if let Err(e) = new_day(&arg) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn parse_day(s: &str) -> Result<u8, String> {
+    let n: u8 = s.trim_start_matches("day").parse()
+        .or(Err(format!("Not a day: {}", s)))?;
+    if !(1..=25).contains(&n) {
+        return Err(format!("Day out of range: {}", n));
+    }
+    Ok(n)
+}
+
+// Insert a [[bin]] entry for the day, keeping the days in order.
+fn register(manifest: &str, name: &str) -> Result<String, String> {
+    let entry = format!("[[bin]]\nname = \"{}\"\npath = \"src/{}.rs\"\n\n", name, name);
+    if manifest.contains(&format!("name = \"{}\"", name)) {
+        return Err(format!("{} is already registered in Cargo.toml", name));
+    }
+
+    let prefix = "[[bin]]\nname = \"";
+    let mut search = 0;
+    while let Some(idx) = manifest[search..].find(prefix) {
+        let idx = search + idx;
+        let other = &manifest[idx + prefix.len()..];
+        let other = &other[..other.find('"').unwrap_or(0)];
+        if other.starts_with("day") && other > name {
+            return Ok(format!("{}{}{}", &manifest[..idx], entry, &manifest[idx..]));
+        }
+        search = idx + 1;
+    }
+
+    let manifest = manifest.trim_end();
+    Ok(format!("{}\n\n{}", manifest, entry.trim_end()) + "\n")
+}
+
+fn new_day(arg: &str) -> Result<(), String> {
+    let name = format!("day{:02}", parse_day(arg)?);
+    let source = format!("src/{}.rs", name);
+    let input = format!("inputs/{}.txt", name);
+    let answer = format!("answers/{}.txt", name);
+
+    for path in [&source, &input, &answer] {
+        if Path::new(path).exists() {
+            return Err(format!("{} already exists, refusing to overwrite", path));
+        }
+    }
+
+    let template = fs::read_to_string("src/template.rs")
+        .or(Err("Couldn't read src/template.rs"))?;
+    let manifest = fs::read_to_string("Cargo.toml")
+        .or(Err("Couldn't read Cargo.toml"))?;
+    let manifest = register(&manifest, &name)?;
+
+    fs::create_dir_all("answers").or(Err("Couldn't create answers/"))?;
+    fs::write(&source, template).or(Err(format!("Couldn't write {}", source)))?;
+    fs::write(&input, "").or(Err(format!("Couldn't write {}", input)))?;
+    fs::write(&answer, "").or(Err(format!("Couldn't write {}", answer)))?;
+    fs::write("Cargo.toml", manifest).or(Err("Couldn't write Cargo.toml"))?;
+
+    println!("Created {}, {} and {}", source, input, answer);
+    Ok(())
+}
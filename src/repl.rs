@@ -0,0 +1,117 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Shared interactive step-through mode for the simulation days. Each
+// day includes this with `mod repl;` and implements `Sim` for its
+// state.
+
+use std::io::{self, BufRead, Write};
+
+pub trait Sim: Clone {
+    fn step(&mut self) -> Result<(), String>;
+    fn show(&self) -> String;
+    // Evaluate a day specific condition for `run until`.
+    fn check(&self, cond: &str) -> Result<bool, String>;
+}
+
+fn print_help() {
+    println!("Commands:");
+    println!("  step [n]          advance n steps (default 1)");
+    println!("  run until <cond>  step until the condition holds");
+    println!("  run until step n  step until n steps have been taken");
+    println!("  show              print the current state");
+    println!("  back [n]          rewind n steps (default 1)");
+    println!("  help              print this message");
+    println!("  quit              leave interactive mode");
+}
+
+fn parse_count(arg: Option<&str>) -> Result<usize, String> {
+    match arg {
+        None => Ok(1),
+        Some(n) => n.parse().or(Err(format!("Not a step count: {}", n))),
+    }
+}
+
+// Advance `state`, pushing the old state onto the history. Stops
+// early if the simulation can't advance any further.
+fn advance<S: Sim>(state: &mut S, history: &mut Vec<S>) -> Result<(), String> {
+    let mut next = state.clone();
+    next.step()?;
+    history.push(std::mem::replace(state, next));
+    Ok(())
+}
+
+fn run_command<S: Sim>(line: &str, state: &mut S, history: &mut Vec<S>)
+                       -> Result<bool, String> {
+    let mut words = line.split_whitespace();
+    match words.next() {
+        None => (),
+        Some("step") => {
+            for _ in 0..parse_count(words.next())? {
+                advance(state, history)?;
+            }
+        },
+        Some("run") => {
+            if words.next() != Some("until") {
+                return Err("Expected `run until <cond>`".to_string());
+            }
+            let cond = words.collect::<Vec<_>>().join(" ");
+            if let Some(n) = cond.strip_prefix("step ") {
+                let n: usize = n.trim().parse()
+                    .or(Err(format!("Not a step count: {}", n)))?;
+                while history.len() < n {
+                    advance(state, history)?;
+                }
+            } else {
+                while !state.check(&cond)? {
+                    advance(state, history)?;
+                }
+            }
+        },
+        Some("show") => print!("{}", state.show()),
+        Some("back") => {
+            for _ in 0..parse_count(words.next())? {
+                *state = history.pop().ok_or("Already at the start")?;
+            }
+        },
+        Some("help") => print_help(),
+        Some("quit") | Some("exit") => return Ok(false),
+        Some(cmd) => return Err(format!("Unknown command: {}", cmd)),
+    }
+    Ok(true)
+}
+
+pub fn run<S: Sim>(init: S) {
+    let mut state = init;
+    let mut history = Vec::new();
+
+    print_help();
+    loop {
+        print!("[step {}]> ", history.len());
+        io::stdout().flush().expect("Couldn't flush stdout");
+
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line).expect("Couldn't read stdin") == 0 {
+            break
+        }
+
+        match run_command(&line, &mut state, &mut history) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+}
@@ -19,17 +19,40 @@ use std::string::String;
 
 fn main() {
     let filename = format!("inputs/{}.txt", module_path!());
-    let text = fs::read(filename).ok().expect("Couldn't open file");
-    let str = String::from_utf8(text).ok().expect("Could parse UTF8 from file");
+    let text = fs::read(filename).expect("Couldn't open file");
+    let str = String::from_utf8(text).expect("Could parse UTF8 from file");
 
     println!("{}", first_part(&str));
     println!("{}", second_part(&str));
 }
 
+fn parse(s: &str) -> Vec<&str> {
+    s.split_terminator('\n').collect()
+}
+
 fn first_part(s: &str) -> usize {
+    let _input = parse(s);
     0
 }
 
 fn second_part(s: &str) -> usize {
+    let _input = parse(s);
     0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "";
+
+    #[test]
+    fn example_first_part() {
+        assert_eq!(first_part(EXAMPLE), 0);
+    }
+
+    #[test]
+    fn example_second_part() {
+        assert_eq!(second_part(EXAMPLE), 0);
+    }
+}